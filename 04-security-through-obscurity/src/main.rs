@@ -1,6 +1,7 @@
 extern crate revord;
 
 use std::collections::BTreeMap;
+use std::env;
 use std::error::Error;
 use std::str::FromStr;
 
@@ -29,6 +30,12 @@ impl Room {
     fn decrypted_id(&self) -> String {
         shift_cipher(&self.id, self.sector as usize)
     }
+
+    fn decrypted_id_with<C>(&self, cipher: &C) -> String
+        where C: Cipher + ?Sized
+    {
+        cipher.decrypt(&self.id)
+    }
 }
 
 impl FromStr for Room {
@@ -58,19 +65,251 @@ impl FromStr for Room {
     }
 }
 
-fn shift_cipher(s: &str, count: usize) -> String {
-    let count = (count % 26) as u8;
+const ALPHABET_LENGTH: u8 = 26;
+
+// Letters are passed around as offsets from `a`, so every cipher
+// works in the range `0..ALPHABET_LENGTH`. The position only counts
+// letters, not the dashes between words. Encrypting separates words
+// with dashes, like a room name, and decrypting separates them with
+// spaces, so each undoes the other. Anything else is left alone.
+trait Cipher {
+    fn encrypt_letter(&self, letter: u8, position: usize) -> u8;
+    fn decrypt_letter(&self, letter: u8, position: usize) -> u8;
+
+    fn encrypt(&self, s: &str) -> String {
+        transform(s, b'-', |letter, position| self.encrypt_letter(letter, position))
+    }
+
+    fn decrypt(&self, s: &str) -> String {
+        transform(s, b' ', |letter, position| self.decrypt_letter(letter, position))
+    }
+}
+
+fn transform<F>(s: &str, separator: u8, mut f: F) -> String
+    where F: FnMut(u8, usize) -> u8
+{
+    let mut position = 0;
     s.bytes().map(|byte| {
-        let shifted_byte = match byte {
-            b'-' => b' ',
-            b => ((((b - b'a') + count) % 26) + b'a'),
+        let transformed_byte = match byte {
+            b'-' | b' ' => separator,
+            b @ b'a'...b'z' => {
+                let letter = f(b - b'a', position);
+                position += 1;
+                letter + b'a'
+            }
+            b => b,
         };
-        shifted_byte as char
+        transformed_byte as char
     }).collect()
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Shift(usize);
+
+impl Shift {
+    fn amount(&self) -> u8 {
+        (self.0 % ALPHABET_LENGTH as usize) as u8
+    }
+}
+
+impl Cipher for Shift {
+    fn encrypt_letter(&self, letter: u8, _: usize) -> u8 {
+        (letter + ALPHABET_LENGTH - self.amount()) % ALPHABET_LENGTH
+    }
+
+    fn decrypt_letter(&self, letter: u8, _: usize) -> u8 {
+        (letter + self.amount()) % ALPHABET_LENGTH
+    }
+}
+
+fn shift_cipher(s: &str, count: usize) -> String {
+    Shift(count).decrypt(s)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Vigenere {
+    key: Vec<u8>,
+}
+
+impl FromStr for Vigenere {
+    type Err = Box<Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err("Empty key".into());
+        }
+
+        let key = s.bytes().map(|b| match b {
+            b'a'...b'z' => Ok(b - b'a'),
+            _ => Err(format!("Invalid key character '{}'", b as char)),
+        }).collect::<Result<_, _>>()?;
+
+        Ok(Vigenere { key: key })
+    }
+}
+
+impl Vigenere {
+    fn key_at(&self, position: usize) -> u8 {
+        self.key[position % self.key.len()]
+    }
+}
+
+impl Cipher for Vigenere {
+    fn encrypt_letter(&self, letter: u8, position: usize) -> u8 {
+        (letter + self.key_at(position)) % ALPHABET_LENGTH
+    }
+
+    fn decrypt_letter(&self, letter: u8, position: usize) -> u8 {
+        (letter + ALPHABET_LENGTH - self.key_at(position)) % ALPHABET_LENGTH
+    }
+}
+
+// The keyword (without repeated letters) starts the cipher alphabet,
+// followed by the remaining letters in order.
+#[derive(Debug, Clone, PartialEq)]
+struct KeyedSubstitution {
+    forward: [u8; 26],
+    backward: [u8; 26],
+}
+
+impl FromStr for KeyedSubstitution {
+    type Err = Box<Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cipher_alphabet = Vec::with_capacity(ALPHABET_LENGTH as usize);
+
+        for b in s.bytes() {
+            match b {
+                b'a'...b'z' => {
+                    let letter = b - b'a';
+                    if !cipher_alphabet.contains(&letter) {
+                        cipher_alphabet.push(letter);
+                    }
+                }
+                _ => return Err(format!("Invalid keyword character '{}'", b as char).into()),
+            }
+        }
+
+        for letter in 0..ALPHABET_LENGTH {
+            if !cipher_alphabet.contains(&letter) {
+                cipher_alphabet.push(letter);
+            }
+        }
+
+        let mut forward = [0; 26];
+        let mut backward = [0; 26];
+        for (plain, &cipher) in cipher_alphabet.iter().enumerate() {
+            forward[plain] = cipher;
+            backward[cipher as usize] = plain as u8;
+        }
+
+        Ok(KeyedSubstitution { forward: forward, backward: backward })
+    }
+}
+
+impl Cipher for KeyedSubstitution {
+    fn encrypt_letter(&self, letter: u8, _: usize) -> u8 {
+        self.forward[letter as usize]
+    }
+
+    fn decrypt_letter(&self, letter: u8, _: usize) -> u8 {
+        self.backward[letter as usize]
+    }
+}
+
+const ENGLISH_INDEX_OF_COINCIDENCE: f64 = 0.0667;
+
+fn index_of_coincidence<I>(letters: I) -> f64
+    where I: IntoIterator<Item = u8>
+{
+    let mut counts = [0usize; 26];
+    let mut total = 0;
+    for letter in letters {
+        counts[letter as usize] += 1;
+        total += 1;
+    }
+
+    if total < 2 {
+        return 0.0;
+    }
+
+    let matching: usize = counts.iter().map(|&c| c * c.saturating_sub(1)).sum();
+    matching as f64 / (total * (total - 1)) as f64
+}
+
+// Splits the ciphertext into one column per key letter; each column
+// was encrypted with a single shift, so it keeps the index of
+// coincidence of English. Multiples of the real key length score just
+// as well, so the shortest length that is close enough to English
+// wins.
+fn guess_vigenere_key_length(ciphertext: &str, max_length: usize) -> Option<usize> {
+    let letters: Vec<_> = ciphertext.bytes()
+        .filter_map(|b| match b {
+            b'a'...b'z' => Some(b - b'a'),
+            _ => None,
+        })
+        .collect();
+
+    let scores: Vec<_> = (1..max_length + 1)
+        .filter(|&length| letters.len() >= length * 2)
+        .map(|length| {
+            let total: f64 = (0..length)
+                .map(|column| {
+                    let column_letters = letters.iter()
+                        .enumerate()
+                        .filter(|&(i, _)| i % length == column)
+                        .map(|(_, &letter)| letter);
+                    index_of_coincidence(column_letters)
+                })
+                .sum();
+            (length, total / length as f64)
+        })
+        .collect();
+
+    let best = scores.iter().map(|&(_, score)| score).fold(0.0, f64::max);
+    let threshold = f64::min(best, ENGLISH_INDEX_OF_COINCIDENCE) * 0.9;
+
+    scores.into_iter()
+        .find(|&(_, score)| score >= threshold)
+        .map(|(length, _)| length)
+}
+
+fn cipher_named(name: &str, key: &str) -> Result<Box<Cipher>, Box<Error>> {
+    match name {
+        "shift" => Ok(Box::new(Shift(key.parse()?))),
+        "vigenere" => Ok(Box::new(key.parse::<Vigenere>()?)),
+        "keyed" => Ok(Box::new(key.parse::<KeyedSubstitution>()?)),
+        _ => Err(format!("Unknown cipher '{}'", name).into()),
+    }
+}
+
+const MAX_GUESSED_KEY_LENGTH: usize = 20;
+
 fn main() {
     let input = include_str!("input.txt");
+    let args: Vec<_> = env::args().skip(1).collect();
+    let mode = args.get(0).map(String::as_str);
+
+    match (mode, args.len()) {
+        (Some("encrypt"), 4) => {
+            let cipher = cipher_named(&args[1], &args[2]).expect("Unable to build cipher");
+            println!("{}", cipher.encrypt(&args[3]));
+            return;
+        }
+        (Some("decrypt"), 4) => {
+            let cipher = cipher_named(&args[1], &args[2]).expect("Unable to build cipher");
+            println!("{}", cipher.decrypt(&args[3]));
+            return;
+        }
+        (Some("guess"), 2) => {
+            match guess_vigenere_key_length(&args[1], MAX_GUESSED_KEY_LENGTH) {
+                Some(length) => println!("The key is probably {} letters long", length),
+                None => println!("The text is too short to guess a key length"),
+            }
+            return;
+        }
+        _ => {}
+    }
 
     let valid_rooms: Vec<_> = input.lines()
         .filter_map(|l| l.parse::<Room>().ok())
@@ -83,6 +322,14 @@ fn main() {
     for r in valid_rooms.iter().find(|r| r.decrypted_id() == "northpole object storage") {
         println!("Storage in sector {}", r.sector_id());
     }
+
+    // `rooms CIPHER KEY` decrypts the real rooms with another cipher
+    if let (Some("rooms"), 3) = (mode, args.len()) {
+        let cipher = cipher_named(&args[1], &args[2]).expect("Unable to build cipher");
+        for r in &valid_rooms {
+            println!("{}: {}", r.sector_id(), r.decrypted_id_with(&*cipher));
+        }
+    }
 }
 
 #[test]
@@ -115,3 +362,67 @@ fn example4() {
 fn shift_cipher_example() {
     assert_eq!(shift_cipher("qzmt-zixmtkozy-ivhz", 343), "very encrypted name");
 }
+
+#[test]
+fn shift_cipher_round_trip() {
+    let cipher = Shift(343);
+    assert_eq!(cipher.encrypt("very encrypted name"), "qzmt-zixmtkozy-ivhz");
+    assert_eq!(cipher.decrypt("qzmt-zixmtkozy-ivhz"), "very encrypted name");
+
+    let room: Room = "aaaaa-bbb-z-y-x-123[abxyz]".parse().expect("Unable to parse room");
+    assert_eq!(Shift(room.sector as usize).encrypt(&room.decrypted_id()), room.id);
+}
+
+#[test]
+fn vigenere_example() {
+    let cipher: Vigenere = "lemon".parse().expect("Unable to parse key");
+    assert_eq!(cipher.decrypt("lxfopv-efrnhr"), "attack atdawn");
+    assert_eq!(cipher.encrypt("attack-atdawn"), "lxfopv-efrnhr");
+}
+
+#[test]
+fn keyed_substitution_example() {
+    let cipher: KeyedSubstitution = "zebras".parse().expect("Unable to parse keyword");
+    assert_eq!(cipher.decrypt("siaa-zq-lkba"), "flee at once");
+    assert_eq!(cipher.encrypt("flee-at-once"), "siaa-zq-lkba");
+}
+
+#[test]
+fn room_decrypts_with_any_cipher() {
+    let room = Room::from_str("lxfopv-efrnhr-123[abcde]").expect("Unable to parse room");
+    let cipher: Vigenere = "lemon".parse().expect("Unable to parse key");
+    assert_eq!(room.decrypted_id_with(&cipher), "attack atdawn");
+
+    let room = Room::from_str("qzmt-zixmtkozy-ivhz-343[zimth]").expect("Unable to parse room");
+    assert_eq!(room.decrypted_id(), "very encrypted name");
+}
+
+#[test]
+fn guess_vigenere_key_length_example() {
+    let plaintext = "it-was-the-best-of-times-it-was-the-worst-of-times-it-was-the-age-of-wisdom-\
+                     it-was-the-age-of-foolishness-it-was-the-epoch-of-belief-it-was-the-epoch-of-\
+                     incredulity-it-was-the-season-of-light-it-was-the-season-of-darkness-it-was-\
+                     the-spring-of-hope-it-was-the-winter-of-despair-we-had-everything-before-us-\
+                     we-had-nothing-before-us-we-were-all-going-direct-to-heaven-we-were-all-going-\
+                     direct-the-other-way";
+    let cipher: Vigenere = "northpole".parse().expect("Unable to parse key");
+    let ciphertext = cipher.encrypt(plaintext);
+
+    assert_eq!(guess_vigenere_key_length(&ciphertext, 20), Some(9));
+}
+
+#[test]
+fn ciphers_leave_other_characters_alone() {
+    let cipher: Vigenere = "lemon".parse().expect("Unable to parse key");
+    assert_eq!(cipher.encrypt("attack at DAWN [42] dawn"), "lxfopv-ef-DAWN-[42]-rnhr");
+    assert_eq!(cipher.decrypt("lxfopv ef DAWN [42] rnhr"), "attack at DAWN [42] dawn");
+    assert_eq!(Shift(1).decrypt("a1z"), "b1a");
+}
+
+#[test]
+fn ciphers_by_name() {
+    let cipher = cipher_named("keyed", "zebras").expect("Unable to build cipher");
+    assert_eq!(cipher.decrypt("siaa-zq-lkba"), "flee at once");
+    assert!(cipher_named("rot13", "").is_err());
+    assert!(cipher_named("shift", "x").is_err());
+}
@@ -2,59 +2,851 @@
 
 extern crate md5;
 extern crate hex;
+extern crate rand;
+extern crate sha1;
+extern crate sha2;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use hex::ToHex;
+use rand::Rng;
 
+#[cfg(test)]
 fn md5hex(s: &str) -> String {
     md5::compute(s.as_bytes()).to_hex()
 }
 
-fn code_stream<'a>(door_id: &'a str) -> impl Iterator<Item = String> + 'a {
-    (0..)
-        .map(move |i| format!("{}{}", door_id, i))
-        .map(|input| md5hex(&input))
-        .filter(|hex| hex.starts_with("00000"))
+#[derive(Debug, Clone, PartialEq)]
+struct Code {
+    index: u64,
+    hash: String,
 }
 
-const PASSWORD_LENGTH: u8 = 8;
+trait HashAlgorithm {
+    type Digest: AsRef<[u8]>;
 
-fn password(door_id: &str) -> String {
-    code_stream(door_id)
-        .flat_map(|hex| hex.chars().nth(5))
-        .take(PASSWORD_LENGTH as usize)
-        .collect()
+    fn name() -> &'static str;
+    fn digest_length() -> usize;
+    fn digest(input: &[u8]) -> Self::Digest;
 }
 
-fn password2(door_id: &str) -> Option<String> {
-    fn sixth_and_seventh_characters(hex: String) -> Option<(char, char)> {
-        let mut c = hex.chars().skip(5).fuse();
-        match (c.next(), c.next()) {
-            (Some(a), Some(b)) => Some((a, b)),
-            _ => None
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Md5;
+
+impl HashAlgorithm for Md5 {
+    type Digest = [u8; 16];
+
+    fn name() -> &'static str { "md5" }
+
+    fn digest_length() -> usize { 16 }
+
+    fn digest(input: &[u8]) -> Self::Digest {
+        let mut digest = [0; 16];
+        digest.copy_from_slice(&md5::compute(input)[..]);
+        digest
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Sha1;
+
+impl HashAlgorithm for Sha1 {
+    type Digest = [u8; 20];
+
+    fn name() -> &'static str { "sha1" }
+
+    fn digest_length() -> usize { 20 }
+
+    fn digest(input: &[u8]) -> Self::Digest {
+        let mut hasher = sha1::Sha1::new();
+        hasher.update(input);
+        hasher.digest().bytes()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Sha256;
+
+impl HashAlgorithm for Sha256 {
+    type Digest = [u8; 32];
+
+    fn name() -> &'static str { "sha256" }
+
+    fn digest_length() -> usize { 32 }
+
+    fn digest(input: &[u8]) -> Self::Digest {
+        use sha2::Digest;
+
+        let mut digest = [0; 32];
+        digest.copy_from_slice(&sha2::Sha256::digest(input));
+        digest
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Difficulty {
+    HexDigits(u32),
+    Bits(u32),
+}
+
+impl Difficulty {
+    fn leading_zero_bits(&self) -> u32 {
+        match *self {
+            Difficulty::HexDigits(digits) => digits * 4,
+            Difficulty::Bits(bits) => bits,
+        }
+    }
+
+    fn is_satisfied_by(&self, digest: &[u8]) -> bool {
+        let bits = self.leading_zero_bits();
+        let whole_bytes = (bits / 8) as usize;
+        let remaining_bits = bits % 8;
+
+        if digest.len() < whole_bytes {
+            return false;
+        }
+
+        let (whole, rest) = digest.split_at(whole_bytes);
+        if !whole.iter().all(|&b| b == 0) {
+            return false;
+        }
+
+        if remaining_bits == 0 {
+            return true;
+        }
+
+        match rest.first() {
+            Some(b) => b.leading_zeros() >= remaining_bits,
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Difficulty::HexDigits(digits) => write!(f, "hex {}", digits),
+            Difficulty::Bits(bits) => write!(f, "bits {}", bits),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = Box<Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let unit = parts.next().ok_or("Missing difficulty unit")?;
+        let amount = parts.next().ok_or("Missing difficulty amount")?.parse()?;
+
+        match unit {
+            "hex" => Ok(Difficulty::HexDigits(amount)),
+            "bits" => Ok(Difficulty::Bits(amount)),
+            _ => Err(format!("Unknown difficulty unit '{}'", unit).into()),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct DoorConfig {
+    difficulty: Difficulty,
+    password_length: u8,
+    // The hash character that `password` uses
+    character_index: usize,
+    // The hash characters that `password2` uses
+    position_index: usize,
+    value_index: usize,
+}
+
+// The position comes from a single hex digit
+const MAX_PASSWORD_LENGTH: u8 = 16;
+
+impl DoorConfig {
+    fn new<H>(difficulty: Difficulty,
+              password_length: u8,
+              character_index: usize,
+              position_index: usize,
+              value_index: usize)
+              -> Result<Self, Box<Error>>
+        where H: HashAlgorithm
+    {
+        if password_length > MAX_PASSWORD_LENGTH {
+            return Err(format!("Password length {} is more than {}",
+                               password_length, MAX_PASSWORD_LENGTH).into());
+        }
+
+        let hex_length = H::digest_length() * 2;
+        for &(name, index) in &[("character", character_index),
+                                ("position", position_index),
+                                ("value", value_index)] {
+            if index >= hex_length {
+                return Err(format!("The {} index {} is outside a {} hash of {} hex digits",
+                                   name, index, H::name(), hex_length).into());
+            }
+        }
+
+        Ok(DoorConfig {
+            difficulty: difficulty,
+            password_length: password_length,
+            character_index: character_index,
+            position_index: position_index,
+            value_index: value_index,
+        })
+    }
+}
+
+impl Default for DoorConfig {
+    fn default() -> Self {
+        DoorConfig {
+            difficulty: Difficulty::HexDigits(5),
+            password_length: 8,
+            character_index: 5,
+            position_index: 5,
+            value_index: 6,
+        }
+    }
+}
+
+// Reuses one input buffer for every index and only builds the hex
+// string for the rare digests that pass the difficulty check.
+// Hashes with MD5 unless another `HashAlgorithm` is given.
+#[derive(Debug, Clone)]
+struct DoorHasher<H = Md5> {
+    input: Vec<u8>,
+    door_id_length: usize,
+    difficulty: Difficulty,
+    algorithm: PhantomData<H>,
+}
+
+impl<H> DoorHasher<H>
+    where H: HashAlgorithm
+{
+    fn new(door_id: &str, difficulty: Difficulty) -> Self {
+        let mut input = Vec::with_capacity(door_id.len() + 20);
+        input.extend_from_slice(door_id.as_bytes());
+
+        DoorHasher {
+            input: input,
+            door_id_length: door_id.len(),
+            difficulty: difficulty,
+            algorithm: PhantomData,
+        }
+    }
+
+    fn interesting_hash(&mut self, index: u64) -> Option<Code> {
+        self.input.truncate(self.door_id_length);
+        write!(&mut self.input, "{}", index).expect("Unable to append index");
+
+        let digest = H::digest(&self.input);
+        let digest = digest.as_ref();
+        if self.difficulty.is_satisfied_by(digest) {
+            Some(Code { index: index, hash: digest.to_hex() })
+        } else {
+            None
+        }
+    }
+}
+
+// Hashes the given indices: `0..` searches everything, while the
+// indices saved by a previous run replay it without a full search.
+fn code_stream<H, I>(door_id: &str, difficulty: Difficulty, indices: I) -> impl Iterator<Item = Code>
+    where H: HashAlgorithm,
+          I: IntoIterator<Item = u64>
+{
+    let mut hasher = DoorHasher::<H>::new(door_id, difficulty);
+    indices.into_iter().flat_map(move |i| hasher.interesting_hash(i))
+}
+
+const DEFAULT_THREADS: usize = 4;
+const CHUNK_SIZE: u64 = 10_000;
+const CHECKPOINT_INTERVAL_SECS: u64 = 5;
+const CHECKPOINT_FILE: &'static str = "door-search.state";
+
+// Everything needed to continue a search: every interesting hash found
+// before `next_index`, so the passwords can be rebuilt from the start.
+#[derive(Debug, Clone, PartialEq)]
+struct Checkpoint {
+    door_id: String,
+    algorithm: String,
+    difficulty: Difficulty,
+    next_index: u64,
+    codes: Vec<Code>,
+}
+
+impl Checkpoint {
+    fn load<P>(path: P) -> Result<Self, Box<Error>>
+        where P: AsRef<Path>
+    {
+        let mut s = String::new();
+        File::open(path)?.read_to_string(&mut s)?;
+        s.parse()
+    }
+
+    // Write to a temporary file first so that an interruption while
+    // saving never leaves a truncated state file behind.
+    fn save<P>(&self, path: P) -> io::Result<()>
+        where P: AsRef<Path>
+    {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut file = File::create(&tmp_path)?;
+            write!(file, "{}", self)?;
+            file.sync_all()?;
+        }
+        fs::rename(tmp_path, path)
+    }
+}
+
+impl fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "door_id {}", self.door_id)?;
+        writeln!(f, "algorithm {}", self.algorithm)?;
+        writeln!(f, "difficulty {}", self.difficulty)?;
+        writeln!(f, "next_index {}", self.next_index)?;
+        for code in &self.codes {
+            writeln!(f, "code {} {}", code.index, code.hash)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Checkpoint {
+    type Err = Box<Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut door_id = None;
+        let mut algorithm = None;
+        let mut difficulty = None;
+        let mut next_index = None;
+        let mut codes = Vec::new();
+
+        for line in s.lines() {
+            let mut parts = line.splitn(2, ' ');
+            let key = parts.next().ok_or("Missing key")?;
+            let value = parts.next().ok_or_else(|| format!("Missing value for '{}'", key))?;
+
+            match key {
+                "door_id" => door_id = Some(value.to_owned()),
+                "algorithm" => algorithm = Some(value.to_owned()),
+                "difficulty" => difficulty = Some(value.parse()?),
+                "next_index" => next_index = Some(value.parse()?),
+                "code" => {
+                    let mut parts = value.split_whitespace();
+                    let index = parts.next().ok_or("Missing code index")?.parse()?;
+                    let hash = parts.next().ok_or("Missing code hash")?.to_owned();
+                    codes.push(Code { index: index, hash: hash });
+                }
+                _ => return Err(format!("Unknown checkpoint key '{}'", key).into()),
+            }
+        }
+
+        Ok(Checkpoint {
+            door_id: door_id.ok_or("Missing door_id")?,
+            // Checkpoints from before other algorithms were supported
+            algorithm: algorithm.unwrap_or_else(|| Md5::name().to_owned()),
+            difficulty: difficulty.ok_or("Missing difficulty")?,
+            next_index: next_index.ok_or("Missing next_index")?,
+            codes: codes,
+        })
+    }
+}
+
+// Produces the same hashes as `code_stream`, but each batch of indices
+// is split into one contiguous chunk per thread.
+#[derive(Debug)]
+struct ParallelCodeStream<H = Md5> {
+    door_id: String,
+    difficulty: Difficulty,
+    threads: usize,
+    chunk_size: u64,
+    next_index: u64,
+    found: VecDeque<Code>,
+    history: Vec<Code>,
+    checkpoint_path: Option<PathBuf>,
+    last_checkpoint: Instant,
+    algorithm: PhantomData<H>,
+}
+
+impl<H> ParallelCodeStream<H>
+    where H: HashAlgorithm + Send + 'static
+{
+    fn new(door_id: &str, difficulty: Difficulty, threads: usize) -> Self {
+        ParallelCodeStream::with_chunk_size(door_id, difficulty, threads, CHUNK_SIZE)
+    }
+
+    fn with_chunk_size(door_id: &str, difficulty: Difficulty, threads: usize, chunk_size: u64) -> Self {
+        ParallelCodeStream {
+            door_id: door_id.to_owned(),
+            difficulty: difficulty,
+            threads: std::cmp::max(threads, 1),
+            chunk_size: std::cmp::max(chunk_size, 1),
+            next_index: 0,
+            found: VecDeque::new(),
+            history: Vec::new(),
+            checkpoint_path: None,
+            last_checkpoint: Instant::now(),
+            algorithm: PhantomData,
+        }
+    }
+
+    fn resume(checkpoint: Checkpoint, door_id: &str, difficulty: Difficulty, threads: usize)
+              -> Result<Self, Box<Error>>
+    {
+        if checkpoint.door_id != door_id {
+            return Err(format!("Checkpoint is for door {}, not {}", checkpoint.door_id, door_id).into());
+        }
+        if checkpoint.algorithm != H::name() {
+            return Err(format!("Checkpoint was created with {}, not {}",
+                               checkpoint.algorithm, H::name()).into());
+        }
+        if checkpoint.difficulty != difficulty {
+            return Err(format!("Checkpoint was created with difficulty {}, not {}",
+                               checkpoint.difficulty, difficulty).into());
+        }
+
+        let mut stream = ParallelCodeStream::new(&checkpoint.door_id, checkpoint.difficulty, threads);
+        stream.next_index = checkpoint.next_index;
+        stream.found = checkpoint.codes.iter().cloned().collect();
+        stream.history = checkpoint.codes;
+        Ok(stream)
+    }
+
+    fn save_checkpoints_to<P>(mut self, path: P) -> Self
+        where P: Into<PathBuf>
+    {
+        self.checkpoint_path = Some(path.into());
+        self
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            door_id: self.door_id.clone(),
+            algorithm: H::name().to_owned(),
+            difficulty: self.difficulty,
+            next_index: self.next_index,
+            codes: self.history.clone(),
+        }
+    }
+
+    fn save_checkpoint_if_due(&mut self) {
+        let interval = Duration::from_secs(CHECKPOINT_INTERVAL_SECS);
+        if self.last_checkpoint.elapsed() < interval {
+            return;
+        }
+
+        if let Some(ref path) = self.checkpoint_path {
+            if let Err(e) = self.checkpoint().save(path) {
+                let _ = writeln!(io::stderr(), "Unable to save checkpoint: {}", e);
+            }
+        }
+        self.last_checkpoint = Instant::now();
+    }
+
+    fn search_next_batch(&mut self) {
+        let handles: Vec<_> = (0..self.threads as u64).map(|t| {
+            let start = self.next_index + t * self.chunk_size;
+            let codes = code_stream::<H, _>(&self.door_id, self.difficulty, start..start + self.chunk_size);
+
+            thread::spawn(move || codes.collect::<Vec<_>>())
+        }).collect();
+
+        // The chunks are handed out in ascending order, so joining the
+        // threads in the order they were spawned keeps the matches in
+        // index order.
+        for handle in handles {
+            let codes = handle.join().expect("Search thread panicked");
+            self.history.extend(codes.iter().cloned());
+            self.found.extend(codes);
+        }
+
+        self.next_index += self.threads as u64 * self.chunk_size;
+        self.save_checkpoint_if_due();
+    }
+}
+
+impl<H> Iterator for ParallelCodeStream<H>
+    where H: HashAlgorithm + Send + 'static
+{
+    type Item = Code;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.found.is_empty() {
+            self.search_next_batch();
+        }
+        self.found.pop_front()
+    }
+}
+
+fn password(door_id: &str, config: DoorConfig) -> String {
+    let mut decoder = PasswordDecoder::new(config);
+    for code in code_stream::<Md5, _>(door_id, config.difficulty, 0..) {
+        decoder.feed(&code);
+        if decoder.is_complete() {
+            break;
+        }
+    }
+    decoder.password
+}
+
+fn password2(door_id: &str, config: DoorConfig) -> Option<String> {
+    let mut decoder = Password2Decoder::new(config);
+    for code in code_stream::<Md5, _>(door_id, config.difficulty, 0..) {
+        decoder.feed(&code);
+        if decoder.is_complete() {
+            break;
+        }
+    }
+    decoder.password()
+}
+
+#[derive(Debug, Clone)]
+struct PasswordDecoder {
+    config: DoorConfig,
+    password: String,
+}
+
+impl PasswordDecoder {
+    fn new(config: DoorConfig) -> Self {
+        PasswordDecoder {
+            config: config,
+            password: String::new(),
+        }
+    }
+
+    fn feed(&mut self, code: &Code) {
+        if self.is_complete() {
+            return;
+        }
+
+        if let Some(chr) = code.hash.chars().nth(self.config.character_index) {
+            self.password.push(chr);
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.password.len() == self.config.password_length as usize
+    }
+}
+
+const PLACEHOLDER: char = '_';
+
+#[derive(Debug, Clone, PartialEq)]
+struct Progress {
+    position: u32,
+    character: char,
+    index: u64,
+    partial: String,
+}
+
+#[derive(Debug, Clone)]
+struct Password2Decoder {
+    config: DoorConfig,
+    code: BTreeMap<u32, char>,
+}
+
+impl Password2Decoder {
+    fn new(config: DoorConfig) -> Self {
+        Password2Decoder {
+            config: config,
+            code: BTreeMap::new(),
+        }
+    }
+
+    fn password_length(&self) -> u32 {
+        self.config.password_length as u32
+    }
+
+    // Returns the progress when the hash fills a new position. The
+    // position is read as a hex digit so that passwords can be up to
+    // 16 characters long; for 10 or fewer this rejects `a`-`f` just as
+    // a decimal digit would.
+    fn feed(&mut self, code: &Code) -> Option<Progress> {
+        let position = code.hash.chars().nth(self.config.position_index);
+        let value = code.hash.chars().nth(self.config.value_index);
+
+        let (pos, chr) = match (position.and_then(|p| p.to_digit(16)), value) {
+            (Some(pos), Some(chr)) => (pos, chr),
+            _ => return None,
+        };
+
+        if pos >= self.password_length() || self.code.contains_key(&pos) {
+            return None;
         }
+        self.code.insert(pos, chr);
+
+        Some(Progress {
+            position: pos,
+            character: chr,
+            index: code.index,
+            partial: (0..self.password_length())
+                .map(|pos| self.code.get(&pos).cloned().unwrap_or(PLACEHOLDER))
+                .collect(),
+        })
     }
 
-    let valid_position_char_pairs = code_stream(door_id)
-        .flat_map(sixth_and_seventh_characters)
-        .flat_map(|(pos, chr)| pos.to_digit(10).map(|pos| (pos, chr)))
-        .filter(|&(pos, _)| pos < PASSWORD_LENGTH as u32);
+    fn is_complete(&self) -> bool {
+        self.code.len() == self.password_length() as usize
+    }
 
-    let mut code = BTreeMap::new();
-    for (pos, chr) in valid_position_char_pairs {
-        code.entry(pos).or_insert(chr);
-        if code.len() == PASSWORD_LENGTH as usize {
+    fn password(&self) -> Option<String> {
+        if self.is_complete() {
             // We know that BTreeMap will iterate in order
-            return Some(code.into_iter().map(|(_, chr)| chr).collect())
+            Some(self.code.values().cloned().collect())
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Passwords {
+    password: String,
+    password2: String,
+    // Every interesting hash seen while decoding, for replaying with `code_stream`
+    indices: Vec<u64>,
+}
+
+// Feeds each interesting hash to both decoders, so the index space
+// only has to be scanned once.
+fn passwords_with_progress<I, F>(codes: I, config: DoorConfig, mut observer: F) -> Option<Passwords>
+    where I: IntoIterator<Item = Code>,
+          F: FnMut(&Progress),
+{
+    let mut first = PasswordDecoder::new(config);
+    let mut second = Password2Decoder::new(config);
+    let mut indices = Vec::new();
+
+    for code in codes {
+        indices.push(code.index);
+
+        first.feed(&code);
+        if let Some(progress) = second.feed(&code) {
+            observer(&progress);
+        }
+
+        if first.is_complete() {
+            if let Some(password2) = second.password() {
+                return Some(Passwords {
+                    password: first.password,
+                    password2: password2,
+                    indices: indices,
+                });
+            }
         }
     }
     None
 }
 
+const INDICES_FILE: &'static str = "door-search.indices";
+
+fn load_indices<P>(path: P) -> Result<Vec<u64>, Box<Error>>
+    where P: AsRef<Path>
+{
+    let mut s = String::new();
+    File::open(path)?.read_to_string(&mut s)?;
+    s.lines().map(|l| l.parse().map_err(Into::into)).collect()
+}
+
+fn save_indices<P>(path: P, indices: &[u64]) -> io::Result<()>
+    where P: AsRef<Path>
+{
+    let mut file = File::create(path)?;
+    for index in indices {
+        writeln!(file, "{}", index)?;
+    }
+    Ok(())
+}
+
+const FRAME_DURATION_MS: u64 = 50;
+const HEX_DIGITS: &'static [u8] = b"0123456789abcdef";
+
+fn render_frame(partial: &str) -> String {
+    partial.chars().map(|c| {
+        if c == PLACEHOLDER {
+            *rand::thread_rng().choose(HEX_DIGITS).expect("No digits to choose from") as char
+        } else {
+            c
+        }
+    }).collect()
+}
+
+// Redraws the password on a single terminal line, showing random
+// digits in every position that has not been decrypted yet.
+struct Cinematic {
+    tx: mpsc::Sender<Progress>,
+    renderer: thread::JoinHandle<()>,
+}
+
+impl Cinematic {
+    fn start(password_length: u8) -> Self {
+        let (tx, rx) = mpsc::channel::<Progress>();
+
+        let renderer = thread::spawn(move || {
+            let placeholders: String = (0..password_length).map(|_| PLACEHOLDER).collect();
+            let mut partial = placeholders;
+            let mut index = 0;
+            let stdout = io::stdout();
+
+            loop {
+                match rx.recv_timeout(Duration::from_millis(FRAME_DURATION_MS)) {
+                    Ok(progress) => {
+                        partial = progress.partial;
+                        index = progress.index;
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+
+                let mut out = stdout.lock();
+                let _ = write!(out, "\rDecrypting: {} (hash #{})", render_frame(&partial), index);
+                let _ = out.flush();
+            }
+
+            let _ = writeln!(stdout.lock(), "");
+        });
+
+        Cinematic { tx: tx, renderer: renderer }
+    }
+
+    fn show(&self, progress: &Progress) {
+        let _ = self.tx.send(progress.clone());
+    }
+
+    fn finish(self) {
+        let Cinematic { tx, renderer } = self;
+        drop(tx);
+        renderer.join().expect("Renderer thread panicked");
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Source {
+    Search,
+    Resume,
+    Replay,
+}
+
+fn solve<H>(door_id: &str, difficulty: Difficulty, password_length: u8, threads: usize, source: Source)
+    where H: HashAlgorithm + Send + 'static
+{
+    let default = DoorConfig::default();
+    let config = DoorConfig::new::<H>(difficulty,
+                                      password_length,
+                                      default.character_index,
+                                      default.position_index,
+                                      default.value_index)
+        .expect("Invalid door configuration");
+
+    let codes: Box<Iterator<Item = Code>> = match source {
+        Source::Replay => {
+            let indices = load_indices(INDICES_FILE).expect("Unable to load indices");
+            Box::new(code_stream::<H, _>(door_id, config.difficulty, indices))
+        }
+        Source::Search => {
+            let codes = ParallelCodeStream::<H>::new(door_id, config.difficulty, threads);
+            Box::new(codes.save_checkpoints_to(CHECKPOINT_FILE))
+        }
+        Source::Resume => {
+            let checkpoint = Checkpoint::load(CHECKPOINT_FILE).expect("Unable to load checkpoint");
+            let codes = ParallelCodeStream::<H>::resume(checkpoint, door_id, config.difficulty, threads)
+                .expect("Unable to resume from checkpoint");
+            Box::new(codes.save_checkpoints_to(CHECKPOINT_FILE))
+        }
+    };
+
+    let cinematic = Cinematic::start(config.password_length);
+    let passwords = passwords_with_progress(codes, config, |progress| cinematic.show(progress));
+    cinematic.finish();
+
+    match passwords {
+        Some(passwords) => {
+            println!("password: {}", passwords.password);
+            println!("password2: {}", passwords.password2);
+            save_indices(INDICES_FILE, &passwords.indices).expect("Unable to save indices");
+        }
+        None => println!("Unable to find both passwords"),
+    }
+}
+
 fn main() {
-    println!("password: {}", password("cxdnnyjw"));
-    println!("password2: {:?}", password2("cxdnnyjw"));
+    let mut threads = DEFAULT_THREADS;
+    let mut source = Source::Search;
+    let mut algorithm = Md5::name().to_owned();
+    let mut difficulty = DoorConfig::default().difficulty;
+    let mut password_length = DoorConfig::default().password_length;
+    let mut only = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &*arg {
+            "--resume" => source = Source::Resume,
+            "--replay" => source = Source::Replay,
+            "--algorithm" => algorithm = args.next().expect("Missing algorithm name"),
+            "--difficulty" => {
+                difficulty = args.next().expect("Missing difficulty")
+                    .parse().expect("Difficulty must look like 'hex 5' or 'bits 20'")
+            }
+            "--only" => only = Some(args.next().expect("Missing password name")),
+            "--length" => {
+                password_length = args.next().expect("Missing password length")
+                    .parse().expect("Password length must be a number")
+            }
+            _ => threads = arg.parse().expect("Thread count must be a number"),
+        }
+    }
+
+    let door_id = "cxdnnyjw";
+
+    // `--only password` or `--only password2` finds just one of them with MD5
+    if let Some(name) = only {
+        assert_eq!(algorithm, Md5::name(), "Only MD5 can find a single password");
+        let default = DoorConfig::default();
+        let config = DoorConfig::new::<Md5>(difficulty,
+                                            password_length,
+                                            default.character_index,
+                                            default.position_index,
+                                            default.value_index)
+            .expect("Invalid door configuration");
+
+        match &*name {
+            "password" => println!("password: {}", password(door_id, config)),
+            "password2" => match password2(door_id, config) {
+                Some(password2) => println!("password2: {}", password2),
+                None => println!("Unable to find password2"),
+            },
+            other => panic!("Unknown password '{}'", other),
+        }
+        return;
+    }
+
+    match &*algorithm {
+        "md5" => solve::<Md5>(door_id, difficulty, password_length, threads, source),
+        "sha1" => solve::<Sha1>(door_id, difficulty, password_length, threads, source),
+        "sha256" => solve::<Sha256>(door_id, difficulty, password_length, threads, source),
+        other => panic!("Unknown hash algorithm '{}'", other),
+    }
+}
+
+// Quick to search for, unlike the default difficulty
+#[cfg(test)]
+fn low_difficulty_config() -> DoorConfig {
+    DoorConfig {
+        difficulty: Difficulty::HexDigits(2),
+        password_length: 5,
+        ..DoorConfig::default()
+    }
 }
 
 #[test]
@@ -63,10 +855,202 @@ fn example_1() {
     assert!(md5hex("abc5017308").starts_with("000008f82"));
     assert!(md5hex("abc5278568").starts_with("00000f"));
 
-    assert_eq!(password("abc"), "18f47a30");
+    assert_eq!(password("abc", DoorConfig::default()), "18f47a30");
 }
 
 #[test]
 fn example_2() {
-    assert_eq!(password2("abc").as_ref().map(String::as_str), Some("05ace8e3"));
+    assert_eq!(password2("abc", DoorConfig::default()).as_ref().map(String::as_str), Some("05ace8e3"));
+}
+
+#[test]
+fn parallel_stream_is_in_index_order() {
+    let difficulty = low_difficulty_config().difficulty;
+    let sequential: Vec<_> = code_stream::<Md5, _>("abc", difficulty, 0..).take(30).collect();
+    let parallel: Vec<_> = ParallelCodeStream::<Md5>::with_chunk_size("abc", difficulty, 3, 100)
+        .take(30)
+        .collect();
+    assert_eq!(parallel, sequential);
+}
+
+#[test]
+fn parallel_passwords() {
+    let config = low_difficulty_config();
+    let codes: ParallelCodeStream = ParallelCodeStream::with_chunk_size("abc", config.difficulty, 4, 100);
+    let passwords = passwords_with_progress(codes, config, |_| {}).expect("Unable to find passwords");
+    assert_eq!(passwords.password, "0b317");
+    assert_eq!(passwords.password2, "96605");
+}
+
+#[test]
+fn password2_progress() {
+    let mut progress = Vec::new();
+    let config = low_difficulty_config();
+    let codes = ParallelCodeStream::<Md5>::with_chunk_size("abc", config.difficulty, 4, 100);
+    let passwords = passwords_with_progress(codes, config, |p| {
+        progress.push(p.clone())
+    });
+
+    assert_eq!(passwords.map(|p| p.password2), Some("96605".into()));
+    assert_eq!(progress.len(), 5);
+    assert_eq!(
+        progress[0],
+        Progress { position: 0, character: '9', index: 18, partial: "9____".into() }
+    );
+    assert_eq!(
+        progress[1],
+        Progress { position: 3, character: '0', index: 444, partial: "9__0_".into() }
+    );
+    assert_eq!(progress[4].partial, "96605");
+}
+
+#[test]
+fn difficulty_in_hex_digits() {
+    assert!(Difficulty::HexDigits(5).is_satisfied_by(&[0x00, 0x00, 0x01]));
+    assert!(!Difficulty::HexDigits(5).is_satisfied_by(&[0x00, 0x00, 0x10]));
+    assert!(!Difficulty::HexDigits(5).is_satisfied_by(&[0x00, 0x00]));
+}
+
+#[test]
+fn difficulty_in_bits() {
+    assert!(Difficulty::Bits(6).is_satisfied_by(&[0x03, 0xff]));
+    assert!(!Difficulty::Bits(6).is_satisfied_by(&[0x04, 0xff]));
+    assert!(Difficulty::Bits(8).is_satisfied_by(&[0x00, 0xff]));
+    assert!(!Difficulty::Bits(8).is_satisfied_by(&[]));
+}
+
+#[test]
+fn low_difficulty_passwords() {
+    let hex_config = low_difficulty_config();
+    let bit_config = DoorConfig { difficulty: Difficulty::Bits(8), ..hex_config };
+
+    assert_eq!(password("abc", hex_config), "0b317");
+    assert_eq!(password("abc", bit_config), "0b317");
+    assert_eq!(password2("abc", hex_config).as_ref().map(String::as_str), Some("96605"));
+    assert_eq!(password2("abc", bit_config).as_ref().map(String::as_str), Some("96605"));
+}
+
+#[test]
+fn door_config_limits() {
+    let difficulty = Difficulty::HexDigits(5);
+    assert_eq!(DoorConfig::new::<Md5>(difficulty, 8, 5, 5, 6).ok(), Some(DoorConfig::default()));
+    assert!(DoorConfig::new::<Md5>(difficulty, 16, 31, 31, 31).is_ok());
+
+    assert!(DoorConfig::new::<Md5>(difficulty, 17, 5, 5, 6).is_err());
+    assert!(DoorConfig::new::<Md5>(difficulty, 8, 32, 5, 6).is_err());
+    assert!(DoorConfig::new::<Md5>(difficulty, 8, 5, 32, 6).is_err());
+    assert!(DoorConfig::new::<Md5>(difficulty, 8, 5, 5, 32).is_err());
+    assert!(DoorConfig::new::<Sha256>(difficulty, 8, 63, 5, 6).is_ok());
+}
+
+#[test]
+fn checkpoint_round_trip() {
+    let checkpoint = Checkpoint {
+        door_id: "abc".into(),
+        algorithm: "md5".into(),
+        difficulty: Difficulty::Bits(20),
+        next_index: 5017309,
+        codes: vec![
+            Code { index: 3231929, hash: "00000155f8105dff7f56ee10fa9b9abd".into() },
+            Code { index: 5017308, hash: "000008f82c5b3924a1ecbebf60344e00".into() },
+        ],
+    };
+
+    let path = env::temp_dir().join("door-search-checkpoint-round-trip.state");
+    checkpoint.save(&path).expect("Unable to save checkpoint");
+    let loaded = Checkpoint::load(&path).expect("Unable to load checkpoint");
+    let _ = fs::remove_file(&path);
+
+    assert_eq!(loaded, checkpoint);
+}
+
+#[test]
+fn resumed_search_gives_same_password() {
+    let config = DoorConfig { difficulty: Difficulty::HexDigits(3), ..DoorConfig::default() };
+    let codes = code_stream::<Md5, _>("abc", config.difficulty, 0..);
+    let expected = passwords_with_progress(codes, config, |_| {});
+    assert!(expected.is_some());
+
+    let mut codes = ParallelCodeStream::<Md5>::with_chunk_size("abc", config.difficulty, 2, 500);
+    codes.by_ref().take(5).count();
+    let checkpoint: Checkpoint = codes.checkpoint().to_string().parse()
+        .expect("Unable to parse checkpoint");
+
+    let resumed = ParallelCodeStream::<Md5>::resume(checkpoint, "abc", config.difficulty, 3)
+        .expect("Unable to resume");
+    assert_eq!(passwords_with_progress(resumed, config, |_| {}), expected);
+}
+
+#[test]
+fn hasher_reuses_its_buffer() {
+    let mut hasher: DoorHasher = DoorHasher::new("abc", Difficulty::HexDigits(5));
+    assert_eq!(hasher.interesting_hash(3231928), None);
+    assert_eq!(
+        hasher.interesting_hash(3231929),
+        Some(Code { index: 3231929, hash: md5hex("abc3231929") })
+    );
+    assert_eq!(hasher.interesting_hash(3231930), None);
+    assert_eq!(hasher.input, b"abc3231930");
+}
+
+#[test]
+fn hash_algorithms() {
+    assert_eq!(Md5::digest(b"abc").to_hex(), "900150983cd24fb0d6963f7d28e17f72");
+    assert_eq!(Sha1::digest(b"abc").to_hex(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    assert_eq!(
+        Sha256::digest(b"abc").to_hex(),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
+
+#[test]
+fn code_stream_with_other_algorithms() {
+    let difficulty = Difficulty::HexDigits(3);
+
+    let code = code_stream::<Sha1, _>("abc", difficulty, 0..).next().expect("No SHA-1 code found");
+    assert_eq!(code.hash, Sha1::digest(format!("abc{}", code.index).as_bytes()).to_hex());
+    assert!(code.hash.starts_with("000"));
+    assert_eq!(code.hash.len(), 40);
+
+    let sequential: Vec<_> = code_stream::<Sha256, _>("abc", difficulty, 0..).take(3).collect();
+    let parallel: Vec<_> = ParallelCodeStream::<Sha256>::with_chunk_size("abc", difficulty, 2, 1_000)
+        .take(3)
+        .collect();
+    assert_eq!(parallel, sequential);
+    assert!(sequential.iter().all(|code| code.hash.len() == 64));
+}
+
+#[test]
+fn resuming_with_a_different_algorithm_fails() {
+    let codes = ParallelCodeStream::<Sha1>::with_chunk_size("abc", Difficulty::HexDigits(1), 1, 10);
+    assert!(ParallelCodeStream::<Md5>::resume(codes.checkpoint(), "abc", Difficulty::HexDigits(1), 1).is_err());
+}
+
+#[test]
+fn resuming_with_a_different_difficulty_fails() {
+    let codes = ParallelCodeStream::<Md5>::with_chunk_size("abc", Difficulty::HexDigits(1), 1, 10);
+    assert!(ParallelCodeStream::<Md5>::resume(codes.checkpoint(), "abc", Difficulty::HexDigits(1), 1).is_ok());
+    assert!(ParallelCodeStream::<Md5>::resume(codes.checkpoint(), "abc", Difficulty::Bits(4), 1).is_err());
+    assert!(ParallelCodeStream::<Md5>::resume(codes.checkpoint(), "abc", Difficulty::HexDigits(2), 1).is_err());
+}
+
+#[test]
+fn resuming_a_different_door_fails() {
+    let codes = ParallelCodeStream::<Md5>::with_chunk_size("abc", Difficulty::HexDigits(1), 1, 10);
+    assert!(ParallelCodeStream::<Md5>::resume(codes.checkpoint(), "abc", Difficulty::HexDigits(1), 1).is_ok());
+    assert!(ParallelCodeStream::<Md5>::resume(codes.checkpoint(), "xyz", Difficulty::HexDigits(1), 1).is_err());
+}
+
+#[test]
+fn both_passwords_in_one_pass() {
+    let config = low_difficulty_config();
+    let codes = ParallelCodeStream::<Md5>::with_chunk_size("abc", config.difficulty, 4, 100);
+    let passwords = passwords_with_progress(codes, config, |_| {}).expect("Unable to find passwords");
+
+    assert_eq!(passwords.password, "0b317");
+    assert_eq!(passwords.password2, "96605");
+    assert_eq!(passwords.indices[0], 18);
+
+    let codes = code_stream::<Md5, _>("abc", config.difficulty, passwords.indices.clone());
+    assert_eq!(passwords_with_progress(codes, config, |_| {}), Some(passwords));
 }
@@ -1,11 +1,16 @@
 use std::collections::BTreeMap;
 use std::collections::btree_map;
+use std::cmp::Ordering;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::iter;
 
 type CharacterFrequency = (char, usize);
 
-fn decode_message<F>(input: &str, ordering: F) -> String
-    where F: Fn(btree_map::IntoIter<char, usize>) -> Option<CharacterFrequency>,
-{
+fn frequencies(input: &str) -> Vec<BTreeMap<char, usize>> {
     let mut by_position = BTreeMap::new();
 
     for line in input.lines() {
@@ -15,8 +20,14 @@ fn decode_message<F>(input: &str, ordering: F) -> String
         }
     }
 
-    by_position.into_iter()
-        .flat_map(|(_, by_letter)| {
+    by_position.into_iter().map(|(_, by_letter)| by_letter).collect()
+}
+
+fn decode_message<F>(input: &str, ordering: F) -> String
+    where F: Fn(btree_map::IntoIter<char, usize>) -> Option<CharacterFrequency>,
+{
+    frequencies(input).into_iter()
+        .flat_map(|by_letter| {
             ordering(by_letter.into_iter())
                 .map(|(chr, _)| chr)
         })
@@ -31,16 +42,508 @@ fn decode_message_min(input: &str) -> String {
     decode_message(input, |i| Iterator::min_by_key(i, |&(_, i)| i))
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Selection {
+    MostCommon,
+    LeastCommon,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum TieBreak {
+    // Matches `decode_message_max` and `decode_message_min`: the last
+    // of the most common characters or the first of the least common.
+    IteratorOrder,
+    LowestCharacter,
+    HighestCharacter,
+    // Tied positions do not get a winner at all
+    Unresolved,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct PositionReport {
+    position: usize,
+    winner: Option<CharacterFrequency>,
+    runner_up: Option<CharacterFrequency>,
+    // Every character that shares the winning count
+    tied: Vec<char>,
+    samples: usize,
+}
+
+impl PositionReport {
+    fn is_tied(&self) -> bool {
+        self.tied.len() > 1
+    }
+
+    // How far ahead the winner is of the runner-up
+    fn margin(&self) -> usize {
+        match (self.winner, self.runner_up) {
+            (Some((_, a)), Some((_, b))) => if a > b { a - b } else { b - a },
+            (Some((_, a)), None) => a,
+            _ => 0,
+        }
+    }
+
+    fn confidence(&self) -> f64 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            self.margin() as f64 / self.samples as f64
+        }
+    }
+}
+
+// Orders every character at a position from best to worst
+fn ranked(by_letter: &BTreeMap<char, usize>, selection: Selection, tie_break: TieBreak) -> Vec<CharacterFrequency> {
+    let mut ranked: Vec<_> = by_letter.iter().map(|(&chr, &count)| (chr, count)).collect();
+
+    let prefer_highest = match (tie_break, selection) {
+        (TieBreak::IteratorOrder, Selection::MostCommon) => true,
+        (TieBreak::HighestCharacter, _) => true,
+        _ => false,
+    };
+
+    ranked.sort_by(|&(a_chr, a_count), &(b_chr, b_count)| {
+        let by_count = match selection {
+            Selection::MostCommon => b_count.cmp(&a_count),
+            Selection::LeastCommon => a_count.cmp(&b_count),
+        };
+        let by_chr = if prefer_highest { b_chr.cmp(&a_chr) } else { a_chr.cmp(&b_chr) };
+        match by_count {
+            Ordering::Equal => by_chr,
+            other => other,
+        }
+    });
+
+    ranked
+}
+
+fn rank_position(position: usize, by_letter: &BTreeMap<char, usize>, selection: Selection, tie_break: TieBreak) -> PositionReport {
+    let ranked = ranked(by_letter, selection, tie_break);
+
+    let best_count = ranked.first().map(|&(_, count)| count);
+    let mut tied: Vec<_> = ranked.iter()
+        .take_while(|&&(_, count)| Some(count) == best_count)
+        .map(|&(chr, _)| chr)
+        .collect();
+    tied.sort();
+
+    let unresolved = tie_break == TieBreak::Unresolved && tied.len() > 1;
+
+    PositionReport {
+        position: position,
+        winner: if unresolved { None } else { ranked.get(0).cloned() },
+        runner_up: ranked.get(1).cloned(),
+        tied: tied,
+        samples: by_letter.values().sum(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Report {
+    positions: Vec<PositionReport>,
+}
+
+impl Report {
+    fn new(frequencies: &[BTreeMap<char, usize>], selection: Selection, tie_break: TieBreak) -> Self {
+        let positions = frequencies.iter()
+            .enumerate()
+            .map(|(i, by_letter)| rank_position(i, by_letter, selection, tie_break))
+            .collect();
+
+        Report { positions: positions }
+    }
+
+    // Unresolved ties leave the message incomplete
+    fn message(&self) -> Option<String> {
+        self.positions.iter().map(|p| p.winner.map(|(chr, _)| chr)).collect()
+    }
+
+    fn tied_positions(&self) -> Vec<usize> {
+        self.positions.iter().filter(|p| p.is_tied()).map(|p| p.position).collect()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn frequency(cf: Option<CharacterFrequency>) -> String {
+            match cf {
+                Some((chr, count)) => format!("{} {:>6}", chr, count),
+                None => format!("- {:>6}", "-"),
+            }
+        }
+
+        writeln!(f, "{:>3} {:<8} {:<9} {:>6} {:>10}", "pos", "winner", "runner-up", "margin", "confidence")?;
+        for p in &self.positions {
+            let confidence = format!("{:.1}%", p.confidence() * 100.0);
+            write!(f, "{:>3} {:<8} {:<9} {:>6} {:>10}",
+                   p.position, frequency(p.winner), frequency(p.runner_up), p.margin(), confidence)?;
+            if p.is_tied() {
+                let tied: Vec<_> = p.tied.iter().map(|c| c.to_string()).collect();
+                write!(f, "  ** TIED: {} **", tied.join(", "))?;
+            }
+            writeln!(f, "")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MisalignedLine {
+    line_number: usize,
+    length: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Alignment {
+    // The most common line length
+    expected_length: usize,
+    misaligned: Vec<MisalignedLine>,
+}
+
+impl Alignment {
+    fn is_aligned(&self) -> bool {
+        self.misaligned.is_empty()
+    }
+}
+
+impl fmt::Display for Alignment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} line(s) are not {} characters long", self.misaligned.len(), self.expected_length)?;
+        for m in &self.misaligned {
+            write!(f, "\n  line {} has {} characters", m.line_number, m.length)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_alignment(input: &str) -> Alignment {
+    let lengths: Vec<_> = input.lines().map(|l| l.trim().chars().count()).collect();
+
+    let mut by_length = BTreeMap::new();
+    for &length in &lengths {
+        *by_length.entry(length).or_insert(0) += 1;
+    }
+
+    // On a tie, prefer the longer length
+    let expected_length = by_length.into_iter()
+        .max_by_key(|&(_, count)| count)
+        .map(|(length, _)| length)
+        .unwrap_or(0);
+
+    let misaligned = lengths.into_iter()
+        .enumerate()
+        .filter(|&(_, length)| length != expected_length)
+        .map(|(i, length)| MisalignedLine { line_number: i + 1, length: length })
+        .collect();
+
+    Alignment {
+        expected_length: expected_length,
+        misaligned: misaligned,
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum AlignmentPolicy {
+    Reject,
+    // Short lines are filled with the character, long lines are cut
+    Pad(char),
+    Ignore,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AlignmentError(Alignment);
+
+impl fmt::Display for AlignmentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Error for AlignmentError {
+    fn description(&self) -> &str {
+        "input lines have different lengths"
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AlignedFrequencies {
+    alignment: Alignment,
+    frequencies: Vec<BTreeMap<char, usize>>,
+}
+
+impl AlignedFrequencies {
+    // How many characters were counted at each position
+    fn samples(&self) -> Vec<usize> {
+        self.frequencies.iter().map(|by_letter| by_letter.values().sum()).collect()
+    }
+}
+
+fn aligned_frequencies(input: &str, policy: AlignmentPolicy) -> Result<AlignedFrequencies, AlignmentError> {
+    let alignment = check_alignment(input);
+    let expected_length = alignment.expected_length;
+
+    if policy == AlignmentPolicy::Reject && !alignment.is_aligned() {
+        return Err(AlignmentError(alignment));
+    }
+
+    let mut frequencies = vec![BTreeMap::new(); expected_length];
+
+    for line in input.lines() {
+        let line = line.trim();
+        let aligned = line.chars().count() == expected_length;
+
+        let chars: Box<Iterator<Item = char>> = match policy {
+            _ if aligned => Box::new(line.chars()),
+            AlignmentPolicy::Pad(filler) => {
+                Box::new(line.chars().chain(iter::repeat(filler)).take(expected_length))
+            }
+            AlignmentPolicy::Ignore | AlignmentPolicy::Reject => continue,
+        };
+
+        for (by_letter, chr) in frequencies.iter_mut().zip(chars) {
+            *by_letter.entry(chr).or_insert(0) += 1;
+        }
+    }
+
+    Ok(AlignedFrequencies {
+        alignment: alignment,
+        frequencies: frequencies,
+    })
+}
+
+// `n` of zero is the same as `decode_message_max`. Fails if any
+// position has fewer than `n + 1` different characters.
+fn decode_message_nth(input: &str, n: usize) -> Option<String> {
+    frequencies(input).iter()
+        .map(|by_letter| {
+            ranked(by_letter, Selection::MostCommon, TieBreak::IteratorOrder)
+                .get(n)
+                .map(|&(chr, _)| chr)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Candidate {
+    message: String,
+    log_likelihood: f64,
+}
+
+impl Candidate {
+    fn likelihood(&self) -> f64 {
+        self.log_likelihood.exp()
+    }
+}
+
+fn by_likelihood(a: &Candidate, b: &Candidate) -> Ordering {
+    // Equally likely messages follow the same tie break as `decode_message_max`
+    match b.log_likelihood.partial_cmp(&a.log_likelihood) {
+        Some(Ordering::Equal) | None => b.message.cmp(&a.message),
+        Some(other) => other,
+    }
+}
+
+// Each position is independent, so keeping only the best `k` partial
+// messages after every position still finds the overall best `k`.
+fn top_messages(input: &str, k: usize) -> Vec<Candidate> {
+    let mut candidates = vec![Candidate { message: String::new(), log_likelihood: 0.0 }];
+
+    for by_letter in frequencies(input) {
+        let samples: usize = by_letter.values().sum();
+
+        let mut extended = Vec::with_capacity(candidates.len() * by_letter.len());
+        for candidate in &candidates {
+            for (&chr, &count) in &by_letter {
+                let mut message = candidate.message.clone();
+                message.push(chr);
+                extended.push(Candidate {
+                    message: message,
+                    log_likelihood: candidate.log_likelihood + (count as f64 / samples as f64).ln(),
+                });
+            }
+        }
+
+        extended.sort_by(by_likelihood);
+        extended.truncate(k);
+        candidates = extended;
+    }
+
+    if k == 0 {
+        candidates.clear();
+    }
+    candidates
+}
+
+const ASCII_LENGTH: usize = 128;
+
+// Counts for a single position. ASCII characters are counted in a
+// fixed array; anything else falls back to a map.
+struct PositionCounts {
+    ascii: [usize; ASCII_LENGTH],
+    other: BTreeMap<char, usize>,
+}
+
+impl PositionCounts {
+    fn new() -> Self {
+        PositionCounts {
+            ascii: [0; ASCII_LENGTH],
+            other: BTreeMap::new(),
+        }
+    }
+
+    fn add(&mut self, chr: char) {
+        let idx = chr as usize;
+        if idx < ASCII_LENGTH {
+            self.ascii[idx] += 1;
+        } else {
+            *self.other.entry(chr).or_insert(0) += 1;
+        }
+    }
+
+    // Iterates in character order, the same as the `BTreeMap` used by
+    // `decode_message`, so ties are broken the same way.
+    fn iter<'a>(&'a self) -> Box<Iterator<Item = CharacterFrequency> + 'a> {
+        let ascii = self.ascii.iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(idx, &count)| (idx as u8 as char, count));
+        let other = self.other.iter().map(|(&chr, &count)| (chr, count));
+        Box::new(ascii.chain(other))
+    }
+}
+
+// Decodes the message without holding the entire input in memory. The
+// current best message can be requested at any point.
+#[derive(Default)]
+struct StreamingDecoder {
+    positions: Vec<PositionCounts>,
+    lines: usize,
+}
+
+impl StreamingDecoder {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn add_line(&mut self, line: &str) {
+        for (i, chr) in line.trim().chars().enumerate() {
+            if i == self.positions.len() {
+                self.positions.push(PositionCounts::new());
+            }
+            self.positions[i].add(chr);
+        }
+        self.lines += 1;
+    }
+
+    fn read_from<R>(&mut self, reader: R) -> io::Result<()>
+        where R: BufRead
+    {
+        self.read_from_with_progress(reader, |_| {})
+    }
+
+    fn read_from_with_progress<R, F>(&mut self, mut reader: R, mut progress: F) -> io::Result<()>
+        where R: BufRead,
+              F: FnMut(&Self),
+    {
+        let mut line = String::new();
+        while reader.read_line(&mut line)? != 0 {
+            self.add_line(&line);
+            progress(self);
+            line.clear();
+        }
+        Ok(())
+    }
+
+    fn lines(&self) -> usize {
+        self.lines
+    }
+
+    fn message_max(&self) -> String {
+        self.positions.iter()
+            .flat_map(|counts| Iterator::max_by_key(counts.iter(), |&(_, i)| i))
+            .map(|(chr, _)| chr)
+            .collect()
+    }
+
+    fn message_min(&self) -> String {
+        self.positions.iter()
+            .flat_map(|counts| Iterator::min_by_key(counts.iter(), |&(_, i)| i))
+            .map(|(chr, _)| chr)
+            .collect()
+    }
+}
+
+const PROGRESS_INTERVAL: usize = 100_000;
+
 fn main() {
-    let input = include_str!("input.txt");
+    let mut decoder = StreamingDecoder::new();
 
-    println!("The message is {}", decode_message_max(input));
-    println!("The message is really {}", decode_message_min(input));
+    match env::args().nth(1) {
+        Some(ref path) if path == "-" => {
+            let stdin = io::stdin();
+            decoder.read_from(stdin.lock()).expect("Unable to read input");
+        }
+        Some(path) => {
+            let file = File::open(path).expect("Unable to open input");
+
+            decoder.read_from_with_progress(BufReader::new(file), |decoder| {
+                if decoder.lines() % PROGRESS_INTERVAL == 0 {
+                    println!("After {} lines the message is {}", decoder.lines(), decoder.message_max());
+                }
+            }).expect("Unable to read input");
+        }
+        None => {
+            let input = include_str!("input.txt");
+
+            println!("The message is {}", decode_message_max(input));
+            println!("The message is really {}", decode_message_min(input));
+
+            let tie_break = match env::var("TIE_BREAK").as_ref().map(String::as_str) {
+                Ok("lowest") => TieBreak::LowestCharacter,
+                Ok("highest") => TieBreak::HighestCharacter,
+                Ok("unresolved") => TieBreak::Unresolved,
+                _ => TieBreak::IteratorOrder,
+            };
+            let policy = match env::var("ALIGNMENT").as_ref().map(String::as_str) {
+                Ok("pad") => AlignmentPolicy::Pad('_'),
+                Ok("ignore") => AlignmentPolicy::Ignore,
+                _ => AlignmentPolicy::Reject,
+            };
+            let aligned = aligned_frequencies(input, policy).expect("Input is not aligned");
+            if !aligned.alignment.is_aligned() {
+                println!("{}", aligned.alignment);
+                println!("Samples per position: {:?}", aligned.samples());
+            }
+
+            for &selection in &[Selection::MostCommon, Selection::LeastCommon] {
+                let report = Report::new(&aligned.frequencies, selection, tie_break);
+                print!("{}", report);
+                match report.message() {
+                    Some(message) => println!("Reported message: {}", message),
+                    None => println!("Unresolved ties at positions {:?}", report.tied_positions()),
+                }
+            }
+
+            if let Some(message) = decode_message_nth(input, 1) {
+                println!("The second most likely characters are {}", message);
+            }
+            for candidate in top_messages(input, 5) {
+                println!("{} (likelihood {:e})", candidate.message, candidate.likelihood());
+            }
+            return;
+        }
+    }
+
+    println!("The message is {}", decoder.message_max());
+    println!("The message is really {}", decoder.message_min());
 }
 
 #[cfg(test)]
 mod test {
-    use super::{decode_message_max, decode_message_min};
+    use super::{decode_message_max, decode_message_min, StreamingDecoder};
+    use super::{frequencies, Report, Selection, TieBreak};
+    use super::{decode_message_nth, top_messages};
+    use super::{aligned_frequencies, check_alignment, AlignmentPolicy, MisalignedLine};
 
     const TEST_INPUT: &'static str = include_str!("test-input.txt");
 
@@ -53,4 +556,161 @@ mod test {
     fn example_2() {
         assert_eq!(decode_message_min(TEST_INPUT), "advent");
     }
+
+    #[test]
+    fn streaming_matches_in_memory() {
+        let mut decoder = StreamingDecoder::new();
+        decoder.read_from(TEST_INPUT.as_bytes()).expect("Unable to read test input");
+
+        assert_eq!(decoder.lines(), 16);
+        assert_eq!(decoder.message_max(), decode_message_max(TEST_INPUT));
+        assert_eq!(decoder.message_min(), decode_message_min(TEST_INPUT));
+    }
+
+    #[test]
+    fn streaming_reports_message_while_reading() {
+        let mut messages = Vec::new();
+        let mut decoder = StreamingDecoder::new();
+        decoder.read_from_with_progress("ab\nab\ncb\ncc\n".as_bytes(), |decoder| {
+            messages.push(decoder.message_max())
+        }).expect("Unable to read input");
+
+        assert_eq!(messages, ["ab", "ab", "ab", "cb"]);
+    }
+
+    #[test]
+    fn streaming_counts_non_ascii_characters() {
+        let input = "äb\näb\naé\n";
+        let mut decoder = StreamingDecoder::new();
+        decoder.read_from(input.as_bytes()).expect("Unable to read input");
+
+        assert_eq!(decoder.message_max(), "äb");
+        assert_eq!(decoder.message_max(), decode_message_max(input));
+        assert_eq!(decoder.message_min(), decode_message_min(input));
+    }
+
+    #[test]
+    fn report_matches_decoded_messages() {
+        let report = Report::new(&frequencies(TEST_INPUT), Selection::MostCommon, TieBreak::IteratorOrder);
+        assert_eq!(report.message(), Some(decode_message_max(TEST_INPUT)));
+
+        let report = Report::new(&frequencies(TEST_INPUT), Selection::LeastCommon, TieBreak::IteratorOrder);
+        assert_eq!(report.message(), Some(decode_message_min(TEST_INPUT)));
+    }
+
+    #[test]
+    fn report_has_runner_up_and_margin() {
+        let report = Report::new(&frequencies("ab\nab\nac\nbc\n"), Selection::MostCommon, TieBreak::IteratorOrder);
+        let first = &report.positions[0];
+
+        assert_eq!(first.winner, Some(('a', 3)));
+        assert_eq!(first.runner_up, Some(('b', 1)));
+        assert_eq!(first.margin(), 2);
+        assert_eq!(first.confidence(), 0.5);
+        assert!(!first.is_tied());
+    }
+
+    #[test]
+    fn report_flags_ties() {
+        let input = "ab\nab\nac\nbc\n";
+
+        let report = Report::new(&frequencies(input), Selection::MostCommon, TieBreak::IteratorOrder);
+        assert_eq!(report.tied_positions(), [1]);
+        assert_eq!(report.positions[1].tied, ['b', 'c']);
+        assert_eq!(report.positions[1].margin(), 0);
+        assert_eq!(report.message().as_ref().map(String::as_str), Some("ac"));
+        assert!(report.to_string().contains("TIED: b, c"));
+
+        let report = Report::new(&frequencies(input), Selection::MostCommon, TieBreak::LowestCharacter);
+        assert_eq!(report.message().as_ref().map(String::as_str), Some("ab"));
+
+        let report = Report::new(&frequencies(input), Selection::MostCommon, TieBreak::Unresolved);
+        assert_eq!(report.message(), None);
+        assert_eq!(report.positions[1].winner, None);
+    }
+
+    #[test]
+    fn least_common_tie_break() {
+        let input = "ab\nab\nac\nbc\n";
+
+        let report = Report::new(&frequencies(input), Selection::LeastCommon, TieBreak::IteratorOrder);
+        assert_eq!(report.message().as_ref().map(String::as_str), Some("bb"));
+        assert_eq!(report.message(), Some(decode_message_min(input)));
+
+        let report = Report::new(&frequencies(input), Selection::LeastCommon, TieBreak::HighestCharacter);
+        assert_eq!(report.message().as_ref().map(String::as_str), Some("bc"));
+    }
+
+    #[test]
+    fn nth_message() {
+        assert_eq!(decode_message_nth(TEST_INPUT, 0), Some(decode_message_max(TEST_INPUT)));
+        assert_eq!(decode_message_nth("ab\nab\nac\nbc\n", 1).as_ref().map(String::as_str), Some("bb"));
+        assert_eq!(decode_message_nth("ab\nab\nac\nbc\n", 2), None);
+    }
+
+    #[test]
+    fn top_message_is_most_common() {
+        let top = top_messages(TEST_INPUT, 3);
+        assert_eq!(top.len(), 3);
+        assert_eq!(top[0].message, "easter");
+        assert!(top[0].log_likelihood >= top[1].log_likelihood);
+        assert!(top[1].log_likelihood >= top[2].log_likelihood);
+    }
+
+    #[test]
+    fn top_messages_are_ranked_by_joint_likelihood() {
+        let top = top_messages("ab\nab\nac\nbc\n", 10);
+        let messages: Vec<_> = top.iter().map(|c| c.message.as_str()).collect();
+
+        assert_eq!(messages, ["ac", "ab", "bc", "bb"]);
+        assert!((top[0].likelihood() - 0.375).abs() < 1e-9);
+        assert!((top[3].likelihood() - 0.125).abs() < 1e-9);
+        assert!(top_messages("ab\nab\nac\nbc\n", 0).is_empty());
+    }
+
+    const MISALIGNED_INPUT: &'static str = "abc\nabc\nab\nabcd\nxbc\n";
+
+    #[test]
+    fn aligned_input_has_no_misaligned_lines() {
+        let alignment = check_alignment(TEST_INPUT);
+        assert_eq!(alignment.expected_length, 6);
+        assert!(alignment.is_aligned());
+    }
+
+    #[test]
+    fn reports_misaligned_lines() {
+        let alignment = check_alignment(MISALIGNED_INPUT);
+        assert_eq!(alignment.expected_length, 3);
+        assert_eq!(alignment.misaligned, [
+            MisalignedLine { line_number: 3, length: 2 },
+            MisalignedLine { line_number: 4, length: 4 },
+        ]);
+    }
+
+    #[test]
+    fn reject_misaligned_lines() {
+        let error = aligned_frequencies(MISALIGNED_INPUT, AlignmentPolicy::Reject)
+            .expect_err("Misaligned input was accepted");
+        assert_eq!(error.0, check_alignment(MISALIGNED_INPUT));
+        assert!(error.to_string().contains("line 3 has 2 characters"));
+
+        assert!(aligned_frequencies(TEST_INPUT, AlignmentPolicy::Reject).is_ok());
+    }
+
+    #[test]
+    fn ignore_misaligned_lines() {
+        let aligned = aligned_frequencies(MISALIGNED_INPUT, AlignmentPolicy::Ignore)
+            .expect("Unable to align input");
+        assert_eq!(aligned.samples(), [3, 3, 3]);
+        assert_eq!(aligned.frequencies[0].get(&'a'), Some(&2));
+    }
+
+    #[test]
+    fn pad_misaligned_lines() {
+        let aligned = aligned_frequencies(MISALIGNED_INPUT, AlignmentPolicy::Pad('_'))
+            .expect("Unable to align input");
+        assert_eq!(aligned.samples(), [5, 5, 5]);
+        assert_eq!(aligned.frequencies[2].get(&'_'), Some(&1));
+        assert_eq!(aligned.frequencies[2].get(&'d'), None);
+    }
 }
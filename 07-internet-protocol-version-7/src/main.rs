@@ -1,37 +1,309 @@
 #![feature(conservative_impl_trait)]
 
+#[macro_use] extern crate lazy_static;
+extern crate rand;
+
 use std::collections::HashSet;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
+
+use rand::{Rng, SeedableRng, XorShiftRng};
 
-fn has_abba(input: &str) -> bool {
-    let chars: Vec<_> = input.chars().collect();
-    chars.windows(4).any(|w| w[0] == w[3] && w[1] == w[2] && w[0] != w[1])
+// Each letter is a variable; different variables must match different characters
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern {
+    letters: Vec<char>,
 }
 
-static DELIMITERS: &'static [char] = &['[', ']'];
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PatternError {
+    Empty,
+    InvalidVariable(char),
+}
 
-// Assumes brackets are always in the correct pairing and never nested
-// Should we create an enum instead of a boolean?
-fn ip_chunks<'a>(input: &'a str) -> impl Iterator<Item = (&'a str, bool)> + 'a {
-    let parts = input.split(DELIMITERS);
-    let outside_hypernet = vec![true, false].into_iter().cycle();
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatternError::Empty => write!(f, "pattern is empty"),
+            PatternError::InvalidVariable(c) => write!(f, "'{}' is not an uppercase variable", c),
+        }
+    }
+}
 
-    parts.zip(outside_hypernet)
+impl Error for PatternError {
+    fn description(&self) -> &str {
+        match *self {
+            PatternError::Empty => "empty pattern",
+            PatternError::InvalidVariable(..) => "invalid pattern variable",
+        }
+    }
 }
 
-fn supports_tls(input: &str) -> bool {
-    let mut has_one_abba = false;
+impl FromStr for Pattern {
+    type Err = PatternError;
 
-    for (part, outside_hypernet) in ip_chunks(input) {
-        if has_abba(part) {
-            if outside_hypernet {
-                has_one_abba = true;
-            } else {
-                return false;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(PatternError::Empty);
+        }
+
+        let letters = s.chars()
+            .map(|c| match c {
+                'A'...'Z' => Ok(c),
+                _ => Err(PatternError::InvalidVariable(c)),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Pattern { letters: letters })
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &c in &self.letters {
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+// Pairs of (variable, value), sorted by variable
+type Bindings = Vec<(char, char)>;
+
+lazy_static! {
+    static ref ABBA: Pattern = "ABBA".parse().expect("Invalid pattern");
+    static ref ABA: Pattern = "ABA".parse().expect("Invalid pattern");
+    static ref BAB: Pattern = ABA.mirror();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PatternMatch {
+    offset: usize,
+    text: String,
+    bindings: Bindings,
+}
+
+impl Pattern {
+    fn abba() -> &'static Self {
+        &ABBA
+    }
+
+    fn aba() -> &'static Self {
+        &ABA
+    }
+
+    fn bab() -> &'static Self {
+        &BAB
+    }
+
+    fn len(&self) -> usize {
+        self.letters.len()
+    }
+
+    // In order of first appearance
+    fn variables(&self) -> Vec<char> {
+        let mut variables = Vec::new();
+        for &c in &self.letters {
+            if !variables.contains(&c) {
+                variables.push(c);
             }
         }
+        variables
+    }
+
+    // Swaps the first and last variables, the second and second-to-last,
+    // and so on. The mirror of ABA is BAB.
+    fn mirror(&self) -> Self {
+        let variables = self.variables();
+        let last = variables.len() - 1;
+        let letters = self.letters.iter()
+            .map(|c| {
+                let i = variables.iter().position(|v| v == c).expect("Unknown variable");
+                variables[last - i]
+            })
+            .collect();
+
+        Pattern { letters: letters }
+    }
+
+    // Fills in `bindings`, so one buffer can be reused for every window
+    fn bind(&self, window: &[char], bindings: &mut Bindings) -> bool {
+        bindings.clear();
+
+        for (&variable, &value) in self.letters.iter().zip(window) {
+            // Variables and values are both unique, so any partial
+            // overlap is a conflict
+            match bindings.iter().find(|&&(var, val)| var == variable || val == value) {
+                Some(&(var, val)) if var == variable && val == value => {}
+                Some(_) => return false,
+                None => bindings.push((variable, value)),
+            }
+        }
+
+        bindings.sort();
+        true
+    }
+
+    fn find(&self, text: &str) -> Vec<PatternMatch> {
+        let chars: Vec<_> = text.chars().collect();
+        let mut bindings = Bindings::with_capacity(self.len());
+        chars.windows(self.len())
+            .enumerate()
+            .filter_map(|(i, window)| {
+                if !self.bind(window, &mut bindings) {
+                    return None;
+                }
+                Some(PatternMatch {
+                    offset: i,
+                    text: window.iter().cloned().collect(),
+                    bindings: bindings.clone(),
+                })
+            })
+            .collect()
     }
 
-    has_one_abba
+    fn is_found_in(&self, text: &str) -> bool {
+        let chars: Vec<_> = text.chars().collect();
+        let mut bindings = Bindings::with_capacity(self.len());
+        chars.windows(self.len()).any(|window| self.bind(window, &mut bindings))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum SegmentKind {
+    Supernet,
+    // Depth 1 is directly inside a single pair of brackets
+    Hypernet { depth: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Segment {
+    text: String,
+    kind: SegmentKind,
+}
+
+impl Segment {
+    fn is_hypernet(&self) -> bool {
+        self.kind != SegmentKind::Supernet
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ParseError {
+    // Byte offsets of the offending bracket
+    UnexpectedClose { position: usize },
+    Unclosed { position: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::UnexpectedClose { position } => {
+                write!(f, "unexpected ']' at position {}", position)
+            }
+            ParseError::Unclosed { position } => {
+                write!(f, "'[' at position {} is never closed", position)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn description(&self) -> &str {
+        match *self {
+            ParseError::UnexpectedClose { .. } => "unexpected closing bracket",
+            ParseError::Unclosed { .. } => "unclosed bracket",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Ipv7Address {
+    segments: Vec<Segment>,
+}
+
+impl FromStr for Ipv7Address {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut open_brackets = Vec::new();
+        let mut start = 0;
+
+        {
+            let mut push_segment = |end, depth| {
+                let text = &s[start..end];
+                if !text.is_empty() {
+                    let kind = match depth {
+                        0 => SegmentKind::Supernet,
+                        depth => SegmentKind::Hypernet { depth: depth },
+                    };
+                    segments.push(Segment { text: text.to_owned(), kind: kind });
+                }
+                start = end + 1;
+            };
+
+            for (i, c) in s.char_indices() {
+                match c {
+                    '[' => {
+                        push_segment(i, open_brackets.len());
+                        open_brackets.push(i);
+                    }
+                    ']' => {
+                        push_segment(i, open_brackets.len());
+                        if open_brackets.pop().is_none() {
+                            return Err(ParseError::UnexpectedClose { position: i });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            push_segment(s.len(), open_brackets.len());
+        }
+
+        match open_brackets.pop() {
+            Some(position) => Err(ParseError::Unclosed { position: position }),
+            None => Ok(Ipv7Address { segments: segments }),
+        }
+    }
+}
+
+impl Ipv7Address {
+    fn supernets<'a>(&'a self) -> impl Iterator<Item = &'a str> + 'a {
+        self.segments.iter().filter(|s| !s.is_hypernet()).map(|s| &s.text[..])
+    }
+
+    // Segments at every nesting depth
+    fn hypernets<'a>(&'a self) -> impl Iterator<Item = &'a str> + 'a {
+        self.segments.iter().filter(|s| s.is_hypernet()).map(|s| &s.text[..])
+    }
+
+    fn supports_tls(&self) -> bool {
+        let abba = Pattern::abba();
+        self.supernets().any(|s| abba.is_found_in(s)) && !self.hypernets().any(|s| abba.is_found_in(s))
+    }
+
+    fn supports_ssl(&self) -> bool {
+        self.has_corresponding(Pattern::aba(), Pattern::bab())
+    }
+
+    // A supernet matches `outside` with the same variable values as a
+    // hypernet matches `inside`
+    fn has_corresponding(&self, outside: &Pattern, inside: &Pattern) -> bool {
+        let outer: HashSet<_> = self.supernets()
+            .flat_map(|s| outside.find(s))
+            .map(|m| m.bindings)
+            .collect();
+
+        self.hypernets()
+            .flat_map(|s| inside.find(s))
+            .any(|m| outer.contains(&m.bindings))
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -41,84 +313,855 @@ struct AbaBabPair {
 }
 
 impl AbaBabPair {
-    fn from_aba(aba: &[char]) -> AbaBabPair {
-        AbaBabPair { a: aba[0], b: aba[1] }
+    fn from_bindings(bindings: &[(char, char)]) -> AbaBabPair {
+        AbaBabPair { a: bindings[0].1, b: bindings[1].1 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Abba {
+    segment: usize,
+    offset: usize,
+    text: String,
+}
+
+// An ABA from a supernet or a BAB from a hypernet
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PairOccurrence {
+    segment: usize,
+    offset: usize,
+    text: String,
+    pair: AbaBabPair,
+    matched: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum TlsRule {
+    AbbaInHypernet { segment: usize },
+    AbbaInSupernet { segment: usize },
+    NoAbba,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum SslRule {
+    Matched { pair: AbaBabPair, aba_segment: usize, bab_segment: usize },
+    NoMatch,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Explanation {
+    segments: Vec<Segment>,
+    abbas: Vec<Abba>,
+    abas: Vec<PairOccurrence>,
+    babs: Vec<PairOccurrence>,
+    tls: TlsRule,
+    ssl: SslRule,
+}
+
+impl Explanation {
+    fn new(address: &Ipv7Address) -> Self {
+        let mut abbas = Vec::new();
+        let mut abas = Vec::new();
+        let mut babs = Vec::new();
+
+        let abba = Pattern::abba();
+        let aba = Pattern::aba();
+        let bab = Pattern::bab();
+
+        for (i, segment) in address.segments.iter().enumerate() {
+            for m in abba.find(&segment.text) {
+                abbas.push(Abba { segment: i, offset: m.offset, text: m.text });
+            }
+
+            let (occurrences, pattern) = if segment.is_hypernet() {
+                (&mut babs, bab)
+            } else {
+                (&mut abas, aba)
+            };
+            for m in pattern.find(&segment.text) {
+                occurrences.push(PairOccurrence {
+                    segment: i,
+                    offset: m.offset,
+                    pair: AbaBabPair::from_bindings(&m.bindings),
+                    text: m.text,
+                    matched: false,
+                });
+            }
+        }
+
+        let aba_pairs: HashSet<_> = abas.iter().map(|o| o.pair).collect();
+        let bab_pairs: HashSet<_> = babs.iter().map(|o| o.pair).collect();
+        for aba in &mut abas {
+            aba.matched = bab_pairs.contains(&aba.pair);
+        }
+        for bab in &mut babs {
+            bab.matched = aba_pairs.contains(&bab.pair);
+        }
+
+        let in_hypernet = |abba: &&Abba| address.segments[abba.segment].is_hypernet();
+        let tls = match abbas.iter().find(&in_hypernet) {
+            Some(abba) => TlsRule::AbbaInHypernet { segment: abba.segment },
+            None => match abbas.first() {
+                Some(abba) => TlsRule::AbbaInSupernet { segment: abba.segment },
+                None => TlsRule::NoAbba,
+            },
+        };
+
+        let ssl = abas.iter()
+            .filter_map(|aba| {
+                babs.iter().find(|bab| bab.pair == aba.pair).map(|bab| {
+                    SslRule::Matched {
+                        pair: aba.pair,
+                        aba_segment: aba.segment,
+                        bab_segment: bab.segment,
+                    }
+                })
+            })
+            .next()
+            .unwrap_or(SslRule::NoMatch);
+
+        Explanation {
+            segments: address.segments.clone(),
+            abbas: abbas,
+            abas: abas,
+            babs: babs,
+            tls: tls,
+            ssl: ssl,
+        }
+    }
+
+    fn supports_tls(&self) -> bool {
+        match self.tls {
+            TlsRule::AbbaInSupernet { .. } => true,
+            _ => false,
+        }
+    }
+
+    fn supports_ssl(&self) -> bool {
+        self.ssl != SslRule::NoMatch
+    }
+
+    // Character ranges of a segment that took part in any match
+    fn highlights(&self, segment: usize) -> Vec<(usize, usize)> {
+        let abbas = self.abbas.iter()
+            .filter(|m| m.segment == segment)
+            .map(|m| (m.offset, 4));
+        let pairs = self.abas.iter().chain(&self.babs)
+            .filter(|m| m.segment == segment && m.matched)
+            .map(|m| (m.offset, 3));
+        abbas.chain(pairs).collect()
+    }
+}
+
+const HIGHLIGHT: &'static str = "\x1b[1;33m";
+const RESET: &'static str = "\x1b[0m";
+
+fn highlight(text: &str, ranges: &[(usize, usize)]) -> String {
+    let mut result = String::new();
+    let mut highlighted = false;
+
+    for (i, c) in text.chars().enumerate() {
+        let wanted = ranges.iter().any(|&(start, len)| start <= i && i < start + len);
+        if wanted != highlighted {
+            result.push_str(if wanted { HIGHLIGHT } else { RESET });
+            highlighted = wanted;
+        }
+        result.push(c);
+    }
+    if highlighted {
+        result.push_str(RESET);
+    }
+
+    result
+}
+
+fn verdict(supported: bool) -> &'static str {
+    if supported { "supported" } else { "not supported" }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            let kind = match segment.kind {
+                SegmentKind::Supernet => "supernet".to_owned(),
+                SegmentKind::Hypernet { depth } => format!("hypernet {}", depth),
+            };
+            writeln!(f, "{:>3} {:<11} {}", i, kind, highlight(&segment.text, &self.highlights(i)))?;
+        }
+
+        write!(f, "TLS {}: ", verdict(self.supports_tls()))?;
+        match self.tls {
+            TlsRule::AbbaInHypernet { segment } => writeln!(f, "ABBA in hypernet segment {}", segment)?,
+            TlsRule::AbbaInSupernet { segment } => writeln!(f, "ABBA in supernet segment {}", segment)?,
+            TlsRule::NoAbba => writeln!(f, "no ABBA")?,
+        }
+        for abba in &self.abbas {
+            writeln!(f, "    ABBA {} in segment {}", abba.text, abba.segment)?;
+        }
+
+        write!(f, "SSL {}: ", verdict(self.supports_ssl()))?;
+        match self.ssl {
+            SslRule::Matched { pair, aba_segment, bab_segment } => {
+                writeln!(f, "{}{}{} in segment {} matches {}{}{} in segment {}",
+                         pair.a, pair.b, pair.a, aba_segment,
+                         pair.b, pair.a, pair.b, bab_segment)?
+            }
+            SslRule::NoMatch => writeln!(f, "no ABA with a corresponding BAB")?,
+        }
+        for (name, occurrence) in self.abas.iter().map(|o| ("ABA", o))
+            .chain(self.babs.iter().map(|o| ("BAB", o)))
+        {
+            let matched = if occurrence.matched { "matched" } else { "unmatched" };
+            writeln!(f, "    {} {} in segment {} {}", name, occurrence.text, occurrence.segment, matched)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Verdict {
+    tls: bool,
+    ssl: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ScanError {
+    UnexpectedClose { position: usize },
+    Unclosed { position: usize },
+    // Only lowercase ASCII letters and brackets are scanned
+    InvalidByte { position: usize, byte: u8 },
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ScanError::UnexpectedClose { position } => {
+                write!(f, "unexpected ']' at position {}", position)
+            }
+            ScanError::Unclosed { position } => {
+                write!(f, "'[' at position {} is never closed", position)
+            }
+            ScanError::InvalidByte { position, byte } => {
+                write!(f, "invalid byte 0x{:02x} at position {}", byte, position)
+            }
+        }
+    }
+}
+
+impl Error for ScanError {
+    fn description(&self) -> &str {
+        match *self {
+            ScanError::UnexpectedClose { .. } => "unexpected closing bracket",
+            ScanError::Unclosed { .. } => "unclosed bracket",
+            ScanError::InvalidByte { .. } => "invalid byte",
+        }
+    }
+}
+
+// Bit `b` of row `a` is set when the pair (a, b) has been seen
+#[derive(Copy, Clone)]
+struct PairBitmap([u32; 26]);
+
+impl PairBitmap {
+    fn new() -> Self {
+        PairBitmap([0; 26])
+    }
+
+    fn insert(&mut self, a: u8, b: u8) {
+        self.0[a as usize] |= 1 << b;
+    }
+
+    fn intersects(&self, other: &PairBitmap) -> bool {
+        self.0.iter().zip(other.0.iter()).any(|(a, b)| a & b != 0)
+    }
+}
+
+// Equivalent to parsing and calling `supports_tls` and `supports_ssl`,
+// but in a single pass without allocating. Unlike parsing, it only
+// accepts lowercase letters and brackets and rejects any other byte.
+fn scan(address: &[u8]) -> Result<Verdict, ScanError> {
+    let mut depth = 0usize;
+    // The most recent letters of the current segment, oldest first
+    let mut window = [0u8; 3];
+    let mut window_len = 0;
+
+    let mut supernet_abba = false;
+    let mut hypernet_abba = false;
+    let mut abas = PairBitmap::new();
+    let mut babs = PairBitmap::new();
+
+    for (i, &byte) in address.iter().enumerate() {
+        match byte {
+            b'[' => {
+                depth += 1;
+                window_len = 0;
+            }
+            b']' => {
+                if depth == 0 {
+                    return Err(ScanError::UnexpectedClose { position: i });
+                }
+                depth -= 1;
+                window_len = 0;
+            }
+            b'a'...b'z' => {
+                let c = byte - b'a';
+                let (w0, w1, w2) = (window[0], window[1], window[2]);
+
+                if window_len >= 3 && w0 == c && w1 == w2 && w1 != c {
+                    if depth == 0 {
+                        supernet_abba = true;
+                    } else {
+                        hypernet_abba = true;
+                    }
+                }
+
+                if window_len >= 2 && w1 == c && w2 != c {
+                    if depth == 0 {
+                        abas.insert(c, w2);
+                    } else {
+                        babs.insert(w2, c);
+                    }
+                }
+
+                window = [w1, w2, c];
+                if window_len < 3 {
+                    window_len += 1;
+                }
+            }
+            _ => return Err(ScanError::InvalidByte { position: i, byte: byte }),
+        }
+    }
+
+    if depth != 0 {
+        return Err(ScanError::Unclosed { position: innermost_unclosed(address) });
     }
 
-    fn from_bab(bab: &[char]) -> AbaBabPair {
-        AbaBabPair { a: bab[1], b: bab[0] }
+    Ok(Verdict {
+        tls: supernet_abba && !hypernet_abba,
+        ssl: abas.intersects(&babs),
+    })
+}
+
+// Finds the bracket that parsing an `Ipv7Address` would report, by
+// walking back from the end instead of keeping a stack
+fn innermost_unclosed(address: &[u8]) -> usize {
+    let mut closed = 0;
+    for (i, &byte) in address.iter().enumerate().rev() {
+        match byte {
+            b']' => closed += 1,
+            b'[' if closed == 0 => return i,
+            b'[' => closed -= 1,
+            _ => {}
+        }
     }
+    panic!("No unclosed bracket");
 }
 
-fn find_aba_bab_pairs<F>(input: &str, collection: &mut HashSet<AbaBabPair>, f: F)
-    where F: Fn(&[char]) -> AbaBabPair
-{
-    let chars: Vec<_> = input.chars().collect();
-    let newly_found = chars.windows(3)
-        .filter(|w| w[0] == w[2] && w[0] != w[1])
-        .map(f);
-    collection.extend(newly_found)
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+struct ScanCounts {
+    addresses: usize,
+    tls: usize,
+    ssl: usize,
 }
 
-fn supports_ssl(input: &str) -> bool {
-    let mut abas = HashSet::new();
-    let mut babs = HashSet::new();
+fn scan_lines<R: BufRead>(mut reader: R) -> Result<ScanCounts, Box<Error>> {
+    let mut counts = ScanCounts::default();
+    let mut line = Vec::new();
 
-    for (part, outside_hypernet) in ip_chunks(input) {
-        if outside_hypernet {
-            find_aba_bab_pairs(part, &mut abas, AbaBabPair::from_aba)
-        } else {
-            find_aba_bab_pairs(part, &mut babs, AbaBabPair::from_bab)
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(counts);
+        }
+        while line.last() == Some(&b'\n') || line.last() == Some(&b'\r') {
+            line.pop();
         }
+
+        let verdict = scan(&line)
+            .map_err(|e| format!("address {}: {}", counts.addresses + 1, e))?;
+
+        counts.addresses += 1;
+        counts.tls += verdict.tls as usize;
+        counts.ssl += verdict.ssl as usize;
     }
+}
 
-    !abas.is_disjoint(&babs)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Profile {
+    TlsOnly,
+    SslOnly,
+    Both,
+    Neither,
+}
+
+impl Profile {
+    fn verdict(&self) -> Verdict {
+        match *self {
+            Profile::TlsOnly => Verdict { tls: true, ssl: false },
+            Profile::SslOnly => Verdict { tls: false, ssl: true },
+            Profile::Both => Verdict { tls: true, ssl: true },
+            Profile::Neither => Verdict { tls: false, ssl: false },
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = Box<Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tls" => Ok(Profile::TlsOnly),
+            "ssl" => Ok(Profile::SslOnly),
+            "both" => Ok(Profile::Both),
+            "neither" => Ok(Profile::Neither),
+            _ => Err(format!("Unknown profile '{}'", s).into()),
+        }
+    }
+}
+
+// Candidates that miss the profile are thrown away
+const MAX_GENERATE_ATTEMPTS: usize = 1000;
+
+struct AddressGenerator {
+    rng: XorShiftRng,
+    segments: usize,
+    segment_length: usize,
+    adversarial: bool,
+}
+
+impl AddressGenerator {
+    // Segments alternate between supernets and hypernets, starting with a supernet
+    fn new(seed: u32, segments: usize, segment_length: usize) -> Self {
+        AddressGenerator {
+            // The seed may not be all zeros
+            rng: XorShiftRng::from_seed([0x193a_6754, 0xa8a7_d469, 0x9783_0e05, seed]),
+            segments: segments,
+            segment_length: segment_length,
+            adversarial: false,
+        }
+    }
+
+    // Adds near-misses like `aaaa` or an ABBA inside a hypernet
+    fn adversarial(mut self, adversarial: bool) -> Self {
+        self.adversarial = adversarial;
+        self
+    }
+
+    fn generate(&mut self, profile: Profile) -> Option<String> {
+        for _ in 0..MAX_GENERATE_ATTEMPTS {
+            let candidate = self.candidate(profile);
+            let address: Ipv7Address = candidate.parse().expect("Generated an invalid address");
+            let verdict = Verdict { tls: address.supports_tls(), ssl: address.supports_ssl() };
+            if verdict == profile.verdict() {
+                return Some(candidate);
+            }
+        }
+
+        None
+    }
+
+    fn candidate(&mut self, profile: Profile) -> String {
+        let segment_length = self.segment_length;
+        let mut segments: Vec<Vec<u8>> = (0..self.segments)
+            .map(|_| (0..segment_length).map(|_| self.letter()).collect())
+            .collect();
+        let verdict = profile.verdict();
+
+        if verdict.tls {
+            let (a, b) = self.distinct_letters();
+            self.plant(&mut segments, Some(false), &[a, b, b, a]);
+        }
+
+        if verdict.ssl {
+            let (a, b) = self.distinct_letters();
+            self.plant(&mut segments, Some(false), &[a, b, a]);
+            self.plant(&mut segments, Some(true), &[b, a, b]);
+        }
+
+        if self.adversarial && self.rng.gen() {
+            let (a, b) = self.distinct_letters();
+            match self.rng.gen_range(0, 3) {
+                1 if !verdict.tls => self.plant(&mut segments, Some(true), &[a, b, b, a]),
+                2 => {
+                    // The same ABA on both sides is not a match
+                    self.plant(&mut segments, Some(false), &[a, b, a]);
+                    self.plant(&mut segments, Some(true), &[a, b, a]);
+                }
+                _ => self.plant(&mut segments, None, &[a, a, a, a]),
+            }
+        }
+
+        let mut address = String::new();
+        for (i, segment) in segments.iter().enumerate() {
+            if i % 2 == 1 {
+                address.push('[');
+            }
+            address.extend(segment.iter().map(|&b| b as char));
+            if i % 2 == 1 {
+                address.push(']');
+            }
+        }
+        address
+    }
+
+    fn letter(&mut self) -> u8 {
+        self.rng.gen_range(b'a', b'z' + 1)
+    }
+
+    fn distinct_letters(&mut self) -> (u8, u8) {
+        let a = self.letter();
+        loop {
+            let b = self.letter();
+            if a != b {
+                return (a, b);
+            }
+        }
+    }
+
+    // Overwrites part of a random segment; `hypernet` of `None` allows either kind
+    fn plant(&mut self, segments: &mut [Vec<u8>], hypernet: Option<bool>, text: &[u8]) {
+        let candidates: Vec<_> = (0..segments.len())
+            .filter(|&i| hypernet.map_or(true, |h| h == (i % 2 == 1)))
+            .filter(|&i| segments[i].len() >= text.len())
+            .collect();
+
+        if let Some(&i) = self.rng.choose(&candidates) {
+            let offset = self.rng.gen_range(0, segments[i].len() - text.len() + 1);
+            segments[i][offset..][..text.len()].copy_from_slice(text);
+        }
+    }
 }
 
 fn main() {
+    let args: Vec<_> = env::args().skip(1).collect();
+
+    if args.first().map(String::as_str) == Some("--scan") {
+        let path = args.get(1).expect("A file of addresses is required");
+        let file = File::open(path).expect("Unable to open addresses");
+        let counts = scan_lines(BufReader::new(file)).expect("Unable to scan addresses");
+        println!("{} of {} support TLS", counts.tls, counts.addresses);
+        println!("{} of {} support SSL", counts.ssl, counts.addresses);
+        return;
+    }
+
+    if args.first().map(String::as_str) == Some("--generate") {
+        let profile: Profile = args.get(1).expect("A profile is required").parse().expect("Invalid profile");
+        let count = args.get(2).map_or(Ok(10), |c| c.parse()).expect("Invalid count");
+        let seed = args.get(3).map_or(Ok(1), |s| s.parse()).expect("Invalid seed");
+
+        let mut generator = AddressGenerator::new(seed, 5, 8).adversarial(true);
+        for _ in 0..count {
+            println!("{}", generator.generate(profile).expect("Unable to generate an address"));
+        }
+        return;
+    }
+
+    if !args.is_empty() {
+        for address in args {
+            match address.parse() {
+                Ok(parsed) => print!("{}\n{}", address, Explanation::new(&parsed)),
+                Err(e) => println!("{}\n  {}", address, e),
+            }
+        }
+        return;
+    }
+
     let input = include_str!("input.txt");
-    let count = input.lines().filter(|s| supports_tls(s)).count();
+    let addresses: Vec<Ipv7Address> = input.lines()
+        .map(|s| s.parse().expect("Invalid address"))
+        .collect();
+
+    let count = addresses.iter().filter(|a| a.supports_tls()).count();
     println!("There were {}", count);
-    let count = input.lines().filter(|s| supports_ssl(s)).count();
+    let count = addresses.iter().filter(|a| a.supports_ssl()).count();
     println!("There were {}", count);
 }
 
+#[cfg(test)]
+fn address(s: &str) -> Ipv7Address {
+    s.parse().expect("Invalid address")
+}
+
 #[test]
 fn example_1() {
-    assert!(supports_tls("abba[mnop]qrst"));
+    assert!(address("abba[mnop]qrst").supports_tls());
 }
 
 #[test]
 fn example_2() {
-    assert!(!supports_tls("abcd[bddb]xyyx"));
+    assert!(!address("abcd[bddb]xyyx").supports_tls());
 }
 
 #[test]
 fn example_3() {
-    assert!(!supports_tls("aaaa[qwer]tyui"));
+    assert!(!address("aaaa[qwer]tyui").supports_tls());
 }
 
 #[test]
 fn example_4() {
-    assert!(supports_tls("ioxxoj[asdfgh]zxcvbn"));
+    assert!(address("ioxxoj[asdfgh]zxcvbn").supports_tls());
 }
 
 #[test]
 fn example_5() {
-    assert!(supports_ssl("aba[bab]xyz"));
+    assert!(address("aba[bab]xyz").supports_ssl());
 }
 
 #[test]
 fn example_6() {
-    assert!(!supports_ssl("xyx[xyx]xyx"));
+    assert!(!address("xyx[xyx]xyx").supports_ssl());
 }
 
 #[test]
 fn example_7() {
-    assert!(supports_ssl("aaa[kek]eke"));
+    assert!(address("aaa[kek]eke").supports_ssl());
 }
 
 #[test]
 fn example_8() {
-    assert!(supports_ssl("zazbz[bzb]cdb"));
+    assert!(address("zazbz[bzb]cdb").supports_ssl());
+}
+
+#[test]
+fn parses_nested_hypernets() {
+    let kinds: Vec<_> = address("ab[cd[ef]gh]ij").segments.into_iter()
+        .map(|s| (s.text, s.kind))
+        .collect();
+
+    assert_eq!(kinds, [
+        ("ab".to_owned(), SegmentKind::Supernet),
+        ("cd".to_owned(), SegmentKind::Hypernet { depth: 1 }),
+        ("ef".to_owned(), SegmentKind::Hypernet { depth: 2 }),
+        ("gh".to_owned(), SegmentKind::Hypernet { depth: 1 }),
+        ("ij".to_owned(), SegmentKind::Supernet),
+    ]);
+}
+
+#[test]
+fn rejects_unbalanced_brackets() {
+    assert_eq!("ab]cd".parse::<Ipv7Address>(), Err(ParseError::UnexpectedClose { position: 2 }));
+    assert_eq!("ab[cd[ef]gh".parse::<Ipv7Address>(), Err(ParseError::Unclosed { position: 2 }));
+}
+
+#[test]
+fn nested_hypernets_are_hypernets() {
+    assert!(!address("abba[xy[oxxo]z]qrst").supports_tls());
+    assert!(address("aba[x[bab]y]xyz").supports_ssl());
+}
+
+#[test]
+fn explains_tls_verdicts() {
+    let explanation = Explanation::new(&address("abcd[bddb]xyyx"));
+    assert_eq!(explanation.tls, TlsRule::AbbaInHypernet { segment: 1 });
+    assert_eq!(explanation.abbas.iter().map(|a| &a.text[..]).collect::<Vec<_>>(), ["bddb", "xyyx"]);
+
+    let explanation = Explanation::new(&address("ioxxoj[asdfgh]zxcvbn"));
+    assert_eq!(explanation.tls, TlsRule::AbbaInSupernet { segment: 0 });
+    assert_eq!(explanation.abbas[0].offset, 1);
+
+    assert_eq!(Explanation::new(&address("aaaa[qwer]tyui")).tls, TlsRule::NoAbba);
+}
+
+#[test]
+fn explains_ssl_verdicts() {
+    let explanation = Explanation::new(&address("zazbz[bzb]cdb"));
+    let pair = AbaBabPair { a: 'z', b: 'b' };
+    assert_eq!(explanation.ssl, SslRule::Matched { pair: pair, aba_segment: 0, bab_segment: 1 });
+
+    let matched: Vec<_> = explanation.abas.iter().map(|o| (&o.text[..], o.matched)).collect();
+    assert_eq!(matched, [("zaz", false), ("zbz", true)]);
+
+    assert_eq!(Explanation::new(&address("xyx[xyx]xyx")).ssl, SslRule::NoMatch);
+}
+
+#[test]
+fn explanations_agree_with_verdicts() {
+    for line in include_str!("input.txt").lines() {
+        let address = address(line);
+        let explanation = Explanation::new(&address);
+        assert_eq!(explanation.supports_tls(), address.supports_tls(), "{}", line);
+        assert_eq!(explanation.supports_ssl(), address.supports_ssl(), "{}", line);
+    }
+}
+
+#[test]
+fn highlights_matches() {
+    assert_eq!(highlight("xabbay", &[(1, 4)]), format!("x{}abba{}y", HIGHLIGHT, RESET));
+    assert_eq!(highlight("aba", &[(0, 3), (1, 3)]), format!("{}aba{}", HIGHLIGHT, RESET));
+}
+
+#[test]
+fn pattern_variables_are_distinct() {
+    let abcba: Pattern = "ABCBA".parse().expect("Invalid pattern");
+    let found: Vec<_> = abcba.find("xyzyx abaxa xyxyx").into_iter().map(|m| m.text).collect();
+    assert_eq!(found, ["xyzyx"]);
+
+    assert!(Pattern::abba().is_found_in("xabba"));
+    assert!(!Pattern::abba().is_found_in("aaaa"));
+}
+
+#[test]
+fn pattern_bindings_are_by_variable() {
+    let matches = Pattern::aba().mirror().find("yxy");
+    assert_eq!(matches[0].bindings, [('A', 'x'), ('B', 'y')]);
+
+    // The buffer is reused, so a failed window must not leak into the next
+    let matches = Pattern::aba().find("abcdcxyx");
+    let bindings: Vec<_> = matches.into_iter().map(|m| m.bindings).collect();
+    assert_eq!(bindings, [vec![('A', 'c'), ('B', 'd')], vec![('A', 'x'), ('B', 'y')]]);
+}
+
+#[test]
+fn builtin_patterns_are_shared() {
+    assert!(Pattern::abba() as *const Pattern == Pattern::abba() as *const Pattern);
+    assert_eq!(Pattern::bab(), &Pattern::aba().mirror());
+}
+
+#[test]
+fn pattern_mirrors() {
+    assert_eq!(Pattern::aba().mirror().to_string(), "BAB");
+    assert_eq!(Pattern::abba().mirror().to_string(), "BAAB");
+    assert_eq!("ABCBA".parse::<Pattern>().map(|p| p.mirror().to_string()), Ok("CBABC".to_owned()));
+}
+
+#[test]
+fn pattern_rejects_invalid_variables() {
+    assert_eq!("".parse::<Pattern>(), Err(PatternError::Empty));
+    assert_eq!("AbA".parse::<Pattern>(), Err(PatternError::InvalidVariable('b')));
+}
+
+#[test]
+fn corresponding_patterns_across_segments() {
+    let abcba: Pattern = "ABCBA".parse().expect("Invalid pattern");
+    let mirror = abcba.mirror();
+
+    assert!(address("qxyzyxq[zyxyz]").has_corresponding(&abcba, &mirror));
+    assert!(!address("qxyzyxq[xyzyx]").has_corresponding(&abcba, &mirror));
+    assert!(!address("zyxyz[abcba]").has_corresponding(&abcba, &mirror));
+}
+
+#[test]
+fn scan_matches_examples() {
+    let examples = [
+        "abba[mnop]qrst", "abcd[bddb]xyyx", "aaaa[qwer]tyui", "ioxxoj[asdfgh]zxcvbn",
+        "aba[bab]xyz", "xyx[xyx]xyx", "aaa[kek]eke", "zazbz[bzb]cdb",
+    ];
+
+    for &example in examples.iter().chain(&include_str!("input.txt").lines().collect::<Vec<_>>()) {
+        let address = address(example);
+        let expected = Verdict { tls: address.supports_tls(), ssl: address.supports_ssl() };
+        assert_eq!(scan(example.as_bytes()), Ok(expected), "{}", example);
+    }
+}
+
+#[test]
+fn scan_matches_random_addresses() {
+    // A small alphabet makes palindromes common. The parser accepts
+    // the uppercase letter and digit, but `scan` does not.
+    const BYTES: &'static [u8] = b"abc[]abc[]A1";
+
+    let mut state: u32 = 0x2545_f491;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state
+    };
+
+    for _ in 0..20_000 {
+        let len = next() % 16;
+        let bytes: Vec<u8> = (0..len).map(|_| BYTES[next() as usize % BYTES.len()]).collect();
+        let text = String::from_utf8(bytes).expect("Invalid UTF-8");
+
+        if let Some(position) = text.bytes().position(|b| b == b'A' || b == b'1') {
+            match scan(text.as_bytes()) {
+                Err(ScanError::InvalidByte { position: actual, byte }) => {
+                    assert_eq!((actual, byte), (position, text.as_bytes()[position]), "{}", text)
+                }
+                Err(ScanError::UnexpectedClose { position: actual }) => {
+                    assert!(actual < position, "{}", text)
+                }
+                Err(e) => panic!("{}: unexpected {:?}", text, e),
+                Ok(verdict) => panic!("{}: {:?} should be an error", text, verdict),
+            }
+            continue;
+        }
+
+        let expected = text.parse::<Ipv7Address>()
+            .map(|a| Verdict { tls: a.supports_tls(), ssl: a.supports_ssl() });
+        match (scan(text.as_bytes()), expected) {
+            (Ok(actual), Ok(expected)) => assert_eq!(actual, expected, "{}", text),
+            (Err(_), Err(_)) => {}
+            (actual, expected) => panic!("{}: {:?} != {:?}", text, actual, expected),
+        }
+    }
+}
+
+#[test]
+fn scan_rejects_invalid_addresses() {
+    assert_eq!(scan(b"ab]c"), Err(ScanError::UnexpectedClose { position: 2 }));
+    assert_eq!(scan(b"ab[c"), Err(ScanError::Unclosed { position: 2 }));
+    assert_eq!(scan(b"ab[cd[ef]gh"), Err(ScanError::Unclosed { position: 2 }));
+    assert_eq!(scan(b"a[b[c]d[e"), Err(ScanError::Unclosed { position: 7 }));
+    assert_eq!("a[b[c]d[e".parse::<Ipv7Address>(), Err(ParseError::Unclosed { position: 7 }));
+    assert_eq!(scan(b"aB"), Err(ScanError::InvalidByte { position: 1, byte: b'B' }));
+}
+
+#[test]
+fn scan_counts_lines() {
+    let counts = scan_lines(&b"abba[mnop]qrst\r\naba[bab]xyz\nxyx[xyx]xyx"[..]).expect("Unable to scan");
+    assert_eq!(counts, ScanCounts { addresses: 3, tls: 1, ssl: 1 });
+
+    assert!(scan_lines(&b"abba\nab]"[..]).is_err());
+}
+
+#[test]
+fn generated_addresses_have_the_requested_profile() {
+    let profiles = [Profile::TlsOnly, Profile::SslOnly, Profile::Both, Profile::Neither];
+
+    for &adversarial in &[false, true] {
+        let mut generator = AddressGenerator::new(42, 5, 8).adversarial(adversarial);
+        for &profile in &profiles {
+            for _ in 0..100 {
+                let generated = generator.generate(profile).expect("Unable to generate an address");
+                assert_eq!(address(&generated).segments.len(), 5);
+                assert_eq!(scan(generated.as_bytes()), Ok(profile.verdict()), "{}", generated);
+            }
+        }
+    }
+}
+
+#[test]
+fn generator_is_seeded() {
+    let generate = |seed| {
+        let mut generator = AddressGenerator::new(seed, 3, 6);
+        (0..10).map(|_| generator.generate(Profile::Both)).collect::<Vec<_>>()
+    };
+
+    assert_eq!(generate(7), generate(7));
+    assert!(generate(7) != generate(8));
+}
+
+#[test]
+fn generator_includes_adversarial_cases() {
+    let mut generator = AddressGenerator::new(0, 5, 8).adversarial(true);
+    let generated: Vec<_> = (0..200)
+        .map(|_| generator.generate(Profile::Neither).expect("Unable to generate an address"))
+        .collect();
+
+    let abba_in_hypernet = |a: &String| match Explanation::new(&address(a)).tls {
+        TlsRule::AbbaInHypernet { .. } => true,
+        _ => false,
+    };
+    let repeated_letter = |a: &String| a.as_bytes().windows(4).any(|w| w.iter().all(|&c| c == w[0]));
+
+    assert!(generated.iter().any(abba_in_hypernet));
+    assert!(generated.iter().any(repeated_letter));
+}
+
+#[test]
+fn generator_gives_up_on_impossible_profiles() {
+    let mut generator = AddressGenerator::new(1, 1, 8);
+    assert_eq!(generator.generate(Profile::SslOnly), None);
 }
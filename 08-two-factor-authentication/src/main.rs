@@ -3,71 +3,205 @@
 extern crate regex;
 
 use std::str::FromStr;
+use std::collections::HashMap;
+use std::env;
 use std::error::Error;
 use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
 
 use regex::Regex;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 enum Instruction {
     Rectangle { x: usize, y: usize },
-    RotateColumn { idx: usize, amount: usize },
-    RotateRow { idx: usize, amount: usize },
+    RectangleOff { x: usize, y: usize },
+    Toggle { x: usize, y: usize },
+    Invert,
+    // Positive amounts move down or right
+    RotateColumn { idx: usize, amount: isize },
+    RotateRow { idx: usize, amount: isize },
+    // Like rotations, but LEDs fall off the edge instead of wrapping
+    ShiftColumn { idx: usize, amount: isize },
+    ShiftRow { idx: usize, amount: isize },
 }
 
 #[macro_use] extern crate lazy_static;
 
 lazy_static! {
+    static ref PARSE_RECTANGLE_OFF: Regex = Regex::new(
+        r"rect off (\d+)x(\d+)"
+    ).expect("Could not compile rectangle off regex");
+
     static ref PARSE_RECTANGLE: Regex = Regex::new(
         r"rect (\d+)x(\d+)"
     ).expect("Could not compile rectangle regex");
 
+    static ref PARSE_TOGGLE: Regex = Regex::new(
+        r"toggle (\d+)x(\d+)"
+    ).expect("Could not compile toggle regex");
+
+    static ref PARSE_INVERT: Regex = Regex::new(
+        r"^invert$"
+    ).expect("Could not compile invert regex");
+
     static ref PARSE_ROTATE_COLUMN: Regex = Regex::new(
-        r"rotate column x=(\d+) by (\d+)"
+        r"rotate column x=(\d+) by (-?\d+)"
     ).expect("Could not compile column regex");
 
     static ref PARSE_ROTATE_ROW: Regex = Regex::new(
-        r"rotate row y=(\d+) by (\d+)"
+        r"rotate row y=(\d+) by (-?\d+)"
     ).expect("Could not compile row regex");
+
+    static ref PARSE_SHIFT_COLUMN: Regex = Regex::new(
+        r"shift column x=(\d+) by (-?\d+)"
+    ).expect("Could not compile shift column regex");
+
+    static ref PARSE_SHIFT_ROW: Regex = Regex::new(
+        r"shift row y=(\d+) by (-?\d+)"
+    ).expect("Could not compile shift row regex");
+
+    static ref PARSE_DISPLAY_HEADER: Regex = Regex::new(
+        r"^(\d+)x(\d+)$"
+    ).expect("Could not compile display header regex");
+}
+
+fn parameters<A, B>(captures: &regex::Captures, name: &str) -> Result<(A, B), Box<Error>>
+    where A: FromStr,
+          B: FromStr,
+          A::Err: Error + 'static,
+          B::Err: Error + 'static,
+{
+    match (captures.at(1), captures.at(2)) {
+        (Some(a), Some(b)) => Ok((a.parse()?, b.parse()?)),
+        _ => Err(format!("Not enough parameters for {}", name).into()),
+    }
 }
 
 impl FromStr for Instruction {
     type Err = Box<Error>;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Some(captures) = PARSE_RECTANGLE.captures(s) {
-            match (captures.at(1), captures.at(2)) {
-                (Some(x), Some(y)) => {
-                    let x = x.parse()?;
-                    let y = y.parse()?;
-                    Ok(Instruction::Rectangle { x, y })
-                },
-                _ => Err("Not enough parameters for rectangle".into())
-            }
+        if let Some(captures) = PARSE_RECTANGLE_OFF.captures(s) {
+            let (x, y) = parameters(&captures, "rectangle off")?;
+            Ok(Instruction::RectangleOff { x, y })
+        } else if let Some(captures) = PARSE_RECTANGLE.captures(s) {
+            let (x, y) = parameters(&captures, "rectangle")?;
+            Ok(Instruction::Rectangle { x, y })
+        } else if let Some(captures) = PARSE_TOGGLE.captures(s) {
+            let (x, y) = parameters(&captures, "toggle")?;
+            Ok(Instruction::Toggle { x, y })
+        } else if PARSE_INVERT.is_match(s) {
+            Ok(Instruction::Invert)
         } else if let Some(captures) = PARSE_ROTATE_COLUMN.captures(s) {
-            match (captures.at(1), captures.at(2)) {
-                (Some(idx), Some(amount)) => {
-                    let idx = idx.parse()?;
-                    let amount = amount.parse()?;
-                    Ok(Instruction::RotateColumn { idx, amount })
-                },
-                _ => Err("Not enough parameters for column".into())
-            }
+            let (idx, amount) = parameters(&captures, "column")?;
+            Ok(Instruction::RotateColumn { idx, amount })
         } else if let Some(captures) = PARSE_ROTATE_ROW.captures(s) {
-            match (captures.at(1), captures.at(2)) {
-                (Some(idx), Some(amount)) => {
-                    let idx = idx.parse()?;
-                    let amount = amount.parse()?;
-                    Ok(Instruction::RotateRow { idx, amount })
-                },
-                _ => Err("Not enough parameters for row".into())
-            }
+            let (idx, amount) = parameters(&captures, "row")?;
+            Ok(Instruction::RotateRow { idx, amount })
+        } else if let Some(captures) = PARSE_SHIFT_COLUMN.captures(s) {
+            let (idx, amount) = parameters(&captures, "shift column")?;
+            Ok(Instruction::ShiftColumn { idx, amount })
+        } else if let Some(captures) = PARSE_SHIFT_ROW.captures(s) {
+            let (idx, amount) = parameters(&captures, "shift row")?;
+            Ok(Instruction::ShiftRow { idx, amount })
         } else {
             Err("Unknown instruction".into())
         }
     }
 }
 
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Instruction::*;
+
+        match *self {
+            Rectangle { x, y } => write!(f, "rect {}x{}", x, y),
+            RectangleOff { x, y } => write!(f, "rect off {}x{}", x, y),
+            Toggle { x, y } => write!(f, "toggle {}x{}", x, y),
+            Invert => write!(f, "invert"),
+            RotateColumn { idx, amount } => write!(f, "rotate column x={} by {}", idx, amount),
+            RotateRow { idx, amount } => write!(f, "rotate row y={} by {}", idx, amount),
+            ShiftColumn { idx, amount } => write!(f, "shift column x={} by {}", idx, amount),
+            ShiftRow { idx, amount } => write!(f, "shift row y={} by {}", idx, amount),
+        }
+    }
+}
+
+// Where index `i` ends up after moving by `amount` in a line of `len`
+fn rotated(i: usize, amount: isize, len: usize) -> usize {
+    let len = len as isize;
+    // Reducing first keeps huge amounts from overflowing
+    let amount = amount % len;
+    (((i as isize + amount) % len + len) % len) as usize
+}
+
+fn shifted(i: usize, amount: isize, len: usize) -> Option<usize> {
+    match (i as isize).checked_add(amount) {
+        Some(new) if 0 <= new && new < len as isize => Some(new as usize),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Bounds {
+    // Instructions that do not fit are rejected
+    Strict,
+    // Only the part of an instruction that fits is applied
+    Clip,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct OutOfBounds {
+    instruction: Instruction,
+    width: usize,
+    height: usize,
+}
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}` does not fit on a {}x{} display", self.instruction, self.width, self.height)
+    }
+}
+
+impl Error for OutOfBounds {
+    fn description(&self) -> &str {
+        "instruction does not fit on the display"
+    }
+}
+
+// The part of the instruction that fits on a display of the given size, if any
+fn clip(instruction: Instruction, width: usize, height: usize) -> Option<Instruction> {
+    use Instruction::*;
+    use std::cmp::min;
+
+    let (w, h) = (width, height);
+
+    match instruction {
+        Rectangle { x, y } => Some(Rectangle { x: min(x, w), y: min(y, h) }),
+        RectangleOff { x, y } => Some(RectangleOff { x: min(x, w), y: min(y, h) }),
+        Toggle { x, y } => Some(Toggle { x: min(x, w), y: min(y, h) }),
+        Invert => Some(Invert),
+        RotateColumn { idx, .. } | ShiftColumn { idx, .. } if idx >= w => None,
+        RotateRow { idx, .. } | ShiftRow { idx, .. } if idx >= h => None,
+        other => Some(other),
+    }
+}
+
+// What to apply to a display of the given size, following `bounds`
+fn fit(instruction: Instruction, bounds: Bounds, width: usize, height: usize)
+       -> Result<Option<Instruction>, OutOfBounds> {
+    match (clip(instruction, width, height), bounds) {
+        (Some(clipped), _) if clipped == instruction => Ok(Some(instruction)),
+        (Some(clipped), Bounds::Clip) => Ok(Some(clipped)),
+        (None, Bounds::Clip) => Ok(None),
+        _ => Err(OutOfBounds { instruction, width, height }),
+    }
+}
+
 #[derive(Clone, PartialEq)]
 struct Display {
     leds: Vec<Vec<bool>>,
@@ -80,44 +214,89 @@ impl Display {
         }
     }
 
-    fn process(&mut self, instruction: Instruction) {
+    fn process(&mut self, instruction: Instruction) -> Result<(), OutOfBounds> {
+        self.process_with(instruction, Bounds::Strict)
+    }
+
+    fn process_with(&mut self, instruction: Instruction, bounds: Bounds) -> Result<(), OutOfBounds> {
+        if let Some(instruction) = fit(instruction, bounds, self.width(), self.height())? {
+            self.apply(instruction);
+        }
+        Ok(())
+    }
+
+    // The instruction must fit on the display
+    fn apply(&mut self, instruction: Instruction) {
         use Instruction::*;
 
         match instruction {
-            Rectangle { x, y } => {
-                for line in &mut self.leds[0..y] {
-                    for pixel in &mut line[0..x] {
-                        *pixel = true;
-                    }
-                }
+            Rectangle { x, y } => self.update_rectangle(x, y, |_| true),
+            RectangleOff { x, y } => self.update_rectangle(x, y, |_| false),
+            Toggle { x, y } => self.update_rectangle(x, y, |on| !on),
+            Invert => {
+                let (w, h) = (self.width(), self.height());
+                self.update_rectangle(w, h, |on| !on)
             }
             RotateColumn { idx, amount } => {
                 let h = self.height();
-                let amount = amount % h;
-                let mut new = vec![false; h];
-
-                for (i, row) in self.leds.iter().enumerate() {
-                    new[(i + amount) % h] = row[idx];
-                }
-
-                for (row, val) in self.leds.iter_mut().zip(new) {
-                    row[idx] = val;
-                }
+                self.move_column(idx, |i| Some(rotated(i, amount, h)))
             }
             RotateRow { idx, amount } => {
                 let w = self.width();
-                let amount = amount % w;
-                let mut new = vec![false; w];
-                for (i, &on) in self.leds[idx].iter().enumerate() {
-                    new[(i + amount) % w] = on;
-                }
-                self.leds[idx] = new;
+                self.move_row(idx, |i| Some(rotated(i, amount, w)))
+            }
+            ShiftColumn { idx, amount } => {
+                let h = self.height();
+                self.move_column(idx, |i| shifted(i, amount, h))
+            }
+            ShiftRow { idx, amount } => {
+                let w = self.width();
+                self.move_row(idx, |i| shifted(i, amount, w))
+            }
+        }
+    }
+
+    fn update_rectangle<F>(&mut self, x: usize, y: usize, f: F)
+        where F: Fn(bool) -> bool
+    {
+        for line in &mut self.leds[0..y] {
+            for pixel in &mut line[0..x] {
+                *pixel = f(*pixel);
             }
         }
     }
 
+    // `to` gives the new position of each LED, or `None` if it falls off
+    fn move_column<F>(&mut self, idx: usize, to: F)
+        where F: Fn(usize) -> Option<usize>
+    {
+        let mut new = vec![false; self.height()];
+
+        for (i, row) in self.leds.iter().enumerate() {
+            if let Some(j) = to(i) {
+                new[j] = row[idx];
+            }
+        }
+
+        for (row, val) in self.leds.iter_mut().zip(new) {
+            row[idx] = val;
+        }
+    }
+
+    fn move_row<F>(&mut self, idx: usize, to: F)
+        where F: Fn(usize) -> Option<usize>
+    {
+        let mut new = vec![false; self.width()];
+        for (i, &on) in self.leds[idx].iter().enumerate() {
+            if let Some(j) = to(i) {
+                new[j] = on;
+            }
+        }
+        self.leds[idx] = new;
+    }
+
     fn width(&self) -> usize {
-        self.leds[0].len()
+        self.leds.first().map_or(0, Vec::len)
     }
 
     fn height(&self) -> usize {
@@ -129,19 +308,124 @@ impl Display {
     }
 }
 
+const LED_ON_CHARS: &'static [char] = &['#', '█'];
+const LED_OFF_CHARS: &'static [char] = &['.', '░'];
+
+// Lines and columns count from 1
+#[derive(Debug, Clone, PartialEq)]
+enum DisplayParseError {
+    UnknownCharacter { line: usize, column: usize, character: char },
+    RaggedRow { line: usize, column: usize, expected: usize, actual: usize },
+    WrongHeight { expected: usize, actual: usize },
+    InvalidHeader { line: usize },
+    EmptySize { width: usize, height: usize },
+}
+
+impl fmt::Display for DisplayParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use DisplayParseError::*;
+
+        match *self {
+            UnknownCharacter { line, column, character } => {
+                write!(f, "{}:{}: unknown LED character {:?}", line, column, character)
+            }
+            RaggedRow { line, column, expected, actual } => {
+                write!(f, "{}:{}: row is {} LEDs wide, expected {}", line, column, actual, expected)
+            }
+            WrongHeight { expected, actual } => {
+                write!(f, "display has {} rows, expected {}", actual, expected)
+            }
+            InvalidHeader { line } => write!(f, "{}:1: invalid display header", line),
+            EmptySize { width, height } => {
+                write!(f, "1:1: display size {}x{} has no LEDs", width, height)
+            }
+        }
+    }
+}
+
+impl Error for DisplayParseError {
+    fn description(&self) -> &str {
+        use DisplayParseError::*;
+
+        match *self {
+            UnknownCharacter { .. } => "unknown LED character",
+            RaggedRow { .. } => "ragged display row",
+            WrongHeight { .. } => "wrong display height",
+            InvalidHeader { .. } => "invalid display header",
+            EmptySize { .. } => "empty display size",
+        }
+    }
+}
+
+// An optional `WIDTHxHEIGHT` header line may come first, and on its own
+// it gives a blank display. Without one, the first row sets the width.
 impl FromStr for Display {
-    type Err = Box<Error>;
+    type Err = DisplayParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let v = s.lines().map(|l| {
-            l.chars().map(|c| c == '#').collect()
-        }).collect();
+        let mut lines = s.lines().enumerate().map(|(i, l)| (i + 1, l)).peekable();
+
+        let mut size = None;
+        if let Some(captures) = lines.peek().and_then(|&(_, l)| PARSE_DISPLAY_HEADER.captures(l)) {
+            let dimension = |i| captures.at(i).and_then(|d| d.parse().ok());
+            size = match (dimension(1), dimension(2)) {
+                (Some(width), Some(height)) if width == 0 || height == 0 => {
+                    return Err(DisplayParseError::EmptySize { width, height })
+                }
+                (Some(width), Some(height)) => Some((width, height)),
+                _ => return Err(DisplayParseError::InvalidHeader { line: 1 }),
+            };
+        }
+        if size.is_some() {
+            lines.next();
+        }
 
-        // TODO: assert rectangular
+        let mut width = size.map(|(width, _)| width);
+        let mut leds = Vec::new();
 
-        Ok(Display {
-            leds: v,
-        })
+        for (line, l) in lines {
+            let row = l.chars()
+                .enumerate()
+                .map(|(i, c)| {
+                    if LED_ON_CHARS.contains(&c) {
+                        Ok(true)
+                    } else if LED_OFF_CHARS.contains(&c) {
+                        Ok(false)
+                    } else {
+                        Err(DisplayParseError::UnknownCharacter { line, column: i + 1, character: c })
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let expected = match width {
+                Some(expected) => expected,
+                None => {
+                    width = Some(row.len());
+                    row.len()
+                }
+            };
+            if row.len() != expected {
+                return Err(DisplayParseError::RaggedRow {
+                    line,
+                    column: std::cmp::min(row.len(), expected) + 1,
+                    expected,
+                    actual: row.len(),
+                });
+            }
+
+            leds.push(row);
+        }
+
+        if let Some((width, height)) = size {
+            if leds.is_empty() {
+                return Ok(Display::new(width, height));
+            }
+            if leds.len() != height {
+                return Err(DisplayParseError::WrongHeight { expected: height, actual: leds.len() });
+            }
+        }
+
+        Ok(Display { leds })
     }
 }
 
@@ -157,97 +441,1780 @@ impl fmt::Debug for Display {
     }
 }
 
-fn main() {
-    let input = include_str!("input.txt");
-    let mut display = Display::new(50, 6);
-    for line in input.lines() {
-        display.process(line.parse().expect("Coldn't parse instruction"));
+const WORD_BITS: usize = 64;
+
+// Word-aligned bits for every row. Bits past the width are always zero.
+#[derive(Debug, Clone, PartialEq)]
+struct PackedDisplay {
+    width: usize,
+    height: usize,
+    stride: usize,
+    words: Vec<u64>,
+    // Reused between instructions to avoid allocating
+    scratch_row: Vec<u64>,
+    scratch_column: Vec<bool>,
+}
+
+// Bits below `x` in word number `word` of a row
+fn prefix_mask(word: usize, x: usize) -> u64 {
+    let start = word * WORD_BITS;
+    if x >= start + WORD_BITS {
+        !0
+    } else if x <= start {
+        0
+    } else {
+        (1 << (x - start)) - 1
     }
-    println!("{:?}", display);
-    println!("There are {} lights", display.count());
 }
 
-#[cfg(test)]
-mod test {
-    use super::{Display, Instruction};
+// Positive amounts move bits towards higher indices
+fn shift_bits(src: &[u64], dst: &mut [u64], amount: isize, or: bool) {
+    let distance = amount.abs() as usize;
+    let (words, bits) = (distance / WORD_BITS, distance % WORD_BITS);
+    let word = |i: isize| if 0 <= i && (i as usize) < src.len() { src[i as usize] } else { 0 };
 
-    fn quick_display(s: &str) -> Display {
-        s.parse().expect("Unable to parse test display")
+    for (i, out) in dst.iter_mut().enumerate() {
+        let i = i as isize;
+        let value = if amount >= 0 {
+            let near = word(i - words as isize) << bits;
+            let far = if bits == 0 { 0 } else { word(i - words as isize - 1) >> (WORD_BITS - bits) };
+            near | far
+        } else {
+            let near = word(i + words as isize) >> bits;
+            let far = if bits == 0 { 0 } else { word(i + words as isize + 1) << (WORD_BITS - bits) };
+            near | far
+        };
+
+        if or {
+            *out |= value;
+        } else {
+            *out = value;
+        }
+    }
+}
+
+impl PackedDisplay {
+    fn new(x: usize, y: usize) -> Self {
+        let stride = (x + WORD_BITS - 1) / WORD_BITS;
+        PackedDisplay {
+            width: x,
+            height: y,
+            stride,
+            words: vec![0; stride * y],
+            scratch_row: Vec::with_capacity(stride),
+            scratch_column: Vec::with_capacity(y),
+        }
     }
 
-    #[test]
-    fn example_1() {
-        let mut display = Display::new(7, 3);
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.words[y * self.stride + x / WORD_BITS] >> (x % WORD_BITS) & 1 == 1
+    }
 
-        display.process(Instruction::Rectangle { x: 3, y: 2 });
+    fn set(&mut self, x: usize, y: usize, on: bool) {
+        let word = &mut self.words[y * self.stride + x / WORD_BITS];
+        let bit = 1 << (x % WORD_BITS);
+        if on {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+    }
 
-        let expected = quick_display("###....\n\
-                                      ###....\n\
-                                      .......");
+    fn process(&mut self, instruction: Instruction, bounds: Bounds) -> Result<(), OutOfBounds> {
+        if let Some(instruction) = fit(instruction, bounds, self.width, self.height)? {
+            self.apply(instruction);
+        }
+        Ok(())
+    }
 
-        assert_eq!(display, expected);
+    // The instruction must fit on the display
+    fn apply(&mut self, instruction: Instruction) {
+        use Instruction::*;
+
+        // An empty display has nothing to change, and rotating a row of
+        // no bits would divide by zero
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        match instruction {
+            Rectangle { x, y } => self.update_rectangle(x, y, |word, mask| word | mask),
+            RectangleOff { x, y } => self.update_rectangle(x, y, |word, mask| word & !mask),
+            Toggle { x, y } => self.update_rectangle(x, y, |word, mask| word ^ mask),
+            Invert => {
+                let (w, h) = (self.width, self.height);
+                self.update_rectangle(w, h, |word, mask| word ^ mask)
+            }
+            RotateColumn { idx, amount } => self.move_column(idx, amount, true),
+            RotateRow { idx, amount } => self.move_row(idx, amount, true),
+            ShiftColumn { idx, amount } => self.move_column(idx, amount, false),
+            ShiftRow { idx, amount } => self.move_row(idx, amount, false),
+        }
     }
 
-    #[test]
-    fn example_2() {
-        let mut display = quick_display("###....\n\
-                                         ###....\n\
-                                         .......");
+    fn update_rectangle<F>(&mut self, x: usize, y: usize, f: F)
+        where F: Fn(u64, u64) -> u64
+    {
+        for row in self.words.chunks_mut(self.stride).take(y) {
+            for (i, word) in row.iter_mut().enumerate() {
+                *word = f(*word, prefix_mask(i, x));
+            }
+        }
+    }
 
-        display.process(Instruction::RotateColumn { idx: 1, amount: 1 });
+    fn move_row(&mut self, idx: usize, amount: isize, wrap: bool) {
+        let width = self.width;
+        let start = idx * self.stride;
+        let row = &mut self.words[start..start + self.stride];
 
-        let expected = quick_display("#.#....\n\
-                                      ###....\n\
-                                      .#.....");
+        self.scratch_row.clear();
+        self.scratch_row.extend_from_slice(row);
 
-        assert_eq!(display, expected);
+        if wrap {
+            let amount = rotated(0, amount, width) as isize;
+            shift_bits(&self.scratch_row, row, amount, false);
+            shift_bits(&self.scratch_row, row, amount - width as isize, true);
+        } else {
+            // Anything past the width clears the row, and clamping keeps
+            // `shift_bits` from taking the absolute value of `isize::MIN`
+            let width = width as isize;
+            let amount = std::cmp::max(-width, std::cmp::min(amount, width));
+            shift_bits(&self.scratch_row, row, amount, false);
+        }
+
+        if let Some(last) = row.last_mut() {
+            *last &= prefix_mask(self.stride - 1, width);
+        }
     }
 
-    #[test]
-    fn example_3() {
-        let mut display = quick_display("#.#....\n\
-                                         ###....\n\
-                                         .#.....");
+    fn move_column(&mut self, idx: usize, amount: isize, wrap: bool) {
+        let height = self.height;
+        let mut column = std::mem::replace(&mut self.scratch_column, Vec::new());
+        column.clear();
+        column.resize(height, false);
+
+        for y in 0..height {
+            let to = if wrap { Some(rotated(y, amount, height)) } else { shifted(y, amount, height) };
+            if let Some(to) = to {
+                column[to] = self.get(idx, y);
+            }
+        }
 
-        display.process(Instruction::RotateRow { idx: 0, amount: 4 });
+        for (y, &on) in column.iter().enumerate() {
+            self.set(idx, y, on);
+        }
 
-        let expected = quick_display("....#.#\n\
-                                      ###....\n\
-                                      .#.....");
+        self.scratch_column = column;
+    }
 
-        assert_eq!(display, expected);
+    fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
     }
+}
 
-    #[test]
-    fn example_4() {
-        let mut display = quick_display("....#.#\n\
-                                         ###....\n\
-                                         .#.....");
+#[cfg(test)]
+impl<'a> From<&'a PackedDisplay> for Display {
+    fn from(packed: &'a PackedDisplay) -> Display {
+        Display {
+            leds: (0..packed.height)
+                .map(|y| (0..packed.width).map(|x| packed.get(x, y)).collect())
+                .collect(),
+        }
+    }
+}
 
-        display.process(Instruction::RotateColumn { idx: 1, amount: 1 });
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Color {
+    r: u8,
+    g: u8,
+    b: u8,
+}
 
-        let expected = quick_display(".#..#.#\n\
-                                      #.#....\n\
-                                      .#.....\n");
+impl Color {
+    fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
 
-        assert_eq!(display, expected);
+const LED_ON: Color = Color { r: 0x5f, g: 0xff, b: 0x5f };
+const LED_OFF: Color = Color { r: 0x1c, g: 0x1c, b: 0x1c };
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct PngOptions {
+    // Each LED becomes a square of `scale` by `scale` pixels
+    scale: usize,
+    on: Color,
+    off: Color,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        PngOptions { scale: 8, on: LED_ON, off: LED_OFF }
     }
+}
 
-    #[test]
-    fn instruction_1() {
-        let actual: Instruction = "rect 100x50".parse().expect("Could not parse instruction");
-        assert_eq!(actual, Instruction::Rectangle { x: 100, y: 50 });
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct SvgOptions {
+    led_size: usize,
+    gap: usize,
+    on: Color,
+    off: Color,
+    background: Color,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions { led_size: 10, gap: 2, on: LED_ON, off: LED_OFF, background: Color { r: 0, g: 0, b: 0 } }
     }
+}
 
-    #[test]
-    fn instruction_2() {
-        let actual: Instruction = "rotate row y=99 by 42".parse().expect("Could not parse instruction");
-        assert_eq!(actual, Instruction::RotateRow { idx: 99, amount: 42 });
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
     }
+    !crc
+}
 
-    #[test]
-    fn instruction_3() {
-        let actual: Instruction = "rotate column x=42 by 99".parse().expect("Could not parse instruction");
-        assert_eq!(actual, Instruction::RotateColumn { idx: 42, amount: 99 });
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn be32(value: u32) -> [u8; 4] {
+    [(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+fn write_png_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&be32(data.len() as u32))?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+
+    let mut crc_input = kind.to_vec();
+    crc_input.extend_from_slice(data);
+    out.write_all(&be32(crc32(&crc_input)))
+}
+
+// A zlib stream of uncompressed deflate blocks; LED images are small
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 0xffff;
+
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        stream.push(last as u8);
+        stream.extend_from_slice(&[len as u8, (len >> 8) as u8, !len as u8, (!len >> 8) as u8]);
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&be32(adler32(data)));
+    stream
+}
+
+impl Display {
+    // Plain (ASCII) portable bitmap, where 1 is a lit LED
+    fn write_pbm<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "P1")?;
+        writeln!(out, "{} {}", self.width(), self.height())?;
+        for line in &self.leds {
+            let bits: Vec<_> = line.iter().map(|&on| if on { "1" } else { "0" }).collect();
+            writeln!(out, "{}", bits.join(" "))?;
+        }
+        Ok(())
+    }
+
+    fn write_png<W: Write>(&self, mut out: W, options: &PngOptions) -> io::Result<()> {
+        let scale = std::cmp::max(options.scale, 1);
+        let width = self.width() * scale;
+        let height = self.height() * scale;
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&be32(width as u32));
+        header.extend_from_slice(&be32(height as u32));
+        // 8-bit truecolor, default compression and filtering, no interlace
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut pixels = Vec::with_capacity(height * (1 + width * 3));
+        for line in &self.leds {
+            let mut row = vec![0];
+            for &on in line {
+                let Color { r, g, b } = if on { options.on } else { options.off };
+                for _ in 0..scale {
+                    row.extend_from_slice(&[r, g, b]);
+                }
+            }
+            for _ in 0..scale {
+                pixels.extend_from_slice(&row);
+            }
+        }
+
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+        write_png_chunk(&mut out, b"IHDR", &header)?;
+        write_png_chunk(&mut out, b"IDAT", &zlib_stored(&pixels))?;
+        write_png_chunk(&mut out, b"IEND", &[])
+    }
+
+    fn write_svg<W: Write>(&self, mut out: W, options: &SvgOptions) -> io::Result<()> {
+        let pitch = options.led_size + options.gap;
+        let width = self.width() * pitch + options.gap;
+        let height = self.height() * pitch + options.gap;
+        let radius = options.led_size as f64 / 2.0;
+
+        writeln!(out, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#,
+                 width, height, width, height)?;
+        writeln!(out, r#"  <rect width="{}" height="{}" fill="{}"/>"#, width, height, options.background.hex())?;
+        for (y, line) in self.leds.iter().enumerate() {
+            for (x, &on) in line.iter().enumerate() {
+                let color = if on { options.on } else { options.off };
+                writeln!(out, r#"  <rect x="{}" y="{}" width="{}" height="{}" rx="{}" fill="{}"/>"#,
+                         options.gap + x * pitch, options.gap + y * pitch,
+                         options.led_size, options.led_size, radius, color.hex())?;
+            }
+        }
+        writeln!(out, "</svg>")
+    }
+
+    // The format is chosen by the file extension
+    fn export<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<Error>> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let out = BufWriter::new(File::create(path)?);
+
+        match extension {
+            "pbm" => self.write_pbm(out)?,
+            "png" => self.write_png(out, &PngOptions::default())?,
+            "svg" => self.write_svg(out, &SvgOptions::default())?,
+            _ => return Err(format!("Unknown image format '{}'", extension).into()),
+        }
+        Ok(())
+    }
+}
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 6;
+
+// The rightmost column is the gap between letters, except for wide letters like Y
+const FONT: &'static [(char, [&'static str; GLYPH_HEIGHT])] = &[
+    ('A', [".##..", "#..#.", "#..#.", "####.", "#..#.", "#..#."]),
+    ('B', ["###..", "#..#.", "###..", "#..#.", "#..#.", "###.."]),
+    ('C', [".##..", "#..#.", "#....", "#....", "#..#.", ".##.."]),
+    ('E', ["####.", "#....", "###..", "#....", "#....", "####."]),
+    ('F', ["####.", "#....", "###..", "#....", "#....", "#...."]),
+    ('G', [".##..", "#..#.", "#....", "#.##.", "#..#.", ".###."]),
+    ('H', ["#..#.", "#..#.", "####.", "#..#.", "#..#.", "#..#."]),
+    ('I', [".###.", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('J', ["..##.", "...#.", "...#.", "...#.", "#..#.", ".##.."]),
+    ('K', ["#..#.", "#.#..", "##...", "#.#..", "#.#..", "#..#."]),
+    ('L', ["#....", "#....", "#....", "#....", "#....", "####."]),
+    ('O', [".##..", "#..#.", "#..#.", "#..#.", "#..#.", ".##.."]),
+    ('P', ["###..", "#..#.", "#..#.", "###..", "#....", "#...."]),
+    ('R', ["###..", "#..#.", "#..#.", "###..", "#.#..", "#..#."]),
+    ('S', [".###.", "#....", "#....", ".##..", "...#.", "###.."]),
+    ('U', ["#..#.", "#..#.", "#..#.", "#..#.", "#..#.", ".##.."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####.", "...#.", "..#..", ".#...", "#....", "####."]),
+    (' ', [".....", ".....", ".....", ".....", ".....", "....."]),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+struct UnknownGlyph {
+    // Position in the text and the first column of the glyph on the display
+    index: usize,
+    column: usize,
+    rows: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum OcrError {
+    WrongHeight { height: usize },
+    UnknownGlyphs { text: String, glyphs: Vec<UnknownGlyph> },
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OcrError::WrongHeight { height } => {
+                write!(f, "display is {} rows tall, glyphs are {}", height, GLYPH_HEIGHT)
+            }
+            OcrError::UnknownGlyphs { ref text, ref glyphs } => {
+                write!(f, "unable to read all of \"{}\"", text)?;
+                for glyph in glyphs {
+                    write!(f, "\nunknown glyph {} at column {}:", glyph.index, glyph.column)?;
+                    for row in &glyph.rows {
+                        write!(f, "\n  {}", row)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for OcrError {
+    fn description(&self) -> &str {
+        match *self {
+            OcrError::WrongHeight { .. } => "display is the wrong height",
+            OcrError::UnknownGlyphs { .. } => "unknown glyphs",
+        }
+    }
+}
+
+impl Display {
+    // Unknown glyphs are shown as `?` in the error's text
+    fn read_text(&self) -> Result<String, OcrError> {
+        if self.height() != GLYPH_HEIGHT {
+            return Err(OcrError::WrongHeight { height: self.height() });
+        }
+
+        let mut text = String::new();
+        let mut unknown = Vec::new();
+
+        for (index, column) in (0..self.width()).filter(|c| c % GLYPH_WIDTH == 0).enumerate() {
+            // A narrow final cell is padded with unlit LEDs
+            let rows: Vec<String> = self.leds.iter()
+                .map(|line| {
+                    (column..column + GLYPH_WIDTH)
+                        .map(|x| if line.get(x) == Some(&true) { '#' } else { '.' })
+                        .collect()
+                })
+                .collect();
+
+            match FONT.iter().find(|&&(_, ref glyph)| glyph.iter().eq(rows.iter())) {
+                Some(&(letter, _)) => text.push(letter),
+                None => {
+                    text.push('?');
+                    unknown.push(UnknownGlyph { index, column, rows });
+                }
+            }
+        }
+
+        if unknown.is_empty() {
+            Ok(text)
+        } else {
+            Err(OcrError::UnknownGlyphs { text, glyphs: unknown })
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Frame {
+    caption: String,
+    display: Display,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Recording {
+    frames: Vec<Frame>,
+}
+
+impl Recording {
+    fn new(initial: &Display) -> Self {
+        Recording {
+            frames: vec![Frame { caption: "start".to_owned(), display: initial.clone() }],
+        }
+    }
+}
+
+impl Display {
+    fn process_recorded(&mut self, instruction: Instruction, bounds: Bounds, recording: &mut Recording)
+                        -> Result<(), OutOfBounds>
+    {
+        self.process_with(instruction, bounds)?;
+        recording.frames.push(Frame { caption: instruction.to_string(), display: self.clone() });
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct GifOptions {
+    scale: usize,
+    // In hundredths of a second
+    frame_delay: u16,
+    on: Color,
+    off: Color,
+}
+
+impl Default for GifOptions {
+    fn default() -> Self {
+        GifOptions { scale: 8, frame_delay: 10, on: LED_ON, off: LED_OFF }
+    }
+}
+
+const GIF_MIN_CODE_SIZE: u8 = 2;
+const GIF_MAX_CODE: u16 = 4096;
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    pending: u32,
+    pending_bits: u8,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter { bytes: Vec::new(), pending: 0, pending_bits: 0 }
+    }
+
+    // Least significant bits first, as GIF expects
+    fn write(&mut self, code: u16, bits: u8) {
+        self.pending |= (code as u32) << self.pending_bits;
+        self.pending_bits += bits;
+        while self.pending_bits >= 8 {
+            self.bytes.push(self.pending as u8);
+            self.pending >>= 8;
+            self.pending_bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.pending_bits > 0 {
+            self.bytes.push(self.pending as u8);
+        }
+        self.bytes
+    }
+}
+
+fn lzw_encode(pixels: &[u8]) -> Vec<u8> {
+    let clear = 1 << GIF_MIN_CODE_SIZE;
+    let end = clear + 1;
+
+    let mut out = BitWriter::new();
+    let mut table = HashMap::new();
+    let mut next_code = end + 1;
+    let mut bits = GIF_MIN_CODE_SIZE + 1;
+    let mut prefix = None;
+
+    out.write(clear, bits);
+    for &pixel in pixels {
+        prefix = match prefix {
+            None => Some(pixel as u16),
+            Some(code) => {
+                if let Some(&extended) = table.get(&(code, pixel)) {
+                    Some(extended)
+                } else {
+                    out.write(code, bits);
+                    if next_code < GIF_MAX_CODE {
+                        table.insert((code, pixel), next_code);
+                        next_code += 1;
+                        if next_code > 1 << bits && bits < 12 {
+                            bits += 1;
+                        }
+                    } else {
+                        out.write(clear, bits);
+                        table.clear();
+                        next_code = end + 1;
+                        bits = GIF_MIN_CODE_SIZE + 1;
+                    }
+                    Some(pixel as u16)
+                }
+            }
+        };
+    }
+    if let Some(code) = prefix {
+        out.write(code, bits);
+    }
+    out.write(end, bits);
+
+    out.finish()
+}
+
+fn write_gif_blocks<W: Write>(out: &mut W, data: &[u8]) -> io::Result<()> {
+    for block in data.chunks(255) {
+        out.write_all(&[block.len() as u8])?;
+        out.write_all(block)?;
+    }
+    out.write_all(&[0])
+}
+
+fn le16(value: usize) -> [u8; 2] {
+    [value as u8, (value >> 8) as u8]
+}
+
+impl Recording {
+    // Captions are stored as GIF comments
+    fn write_gif<W: Write>(&self, mut out: W, options: &GifOptions) -> io::Result<()> {
+        let first = match self.frames.first() {
+            Some(frame) => &frame.display,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "no frames recorded")),
+        };
+        let scale = std::cmp::max(options.scale, 1);
+        let width = first.width() * scale;
+        let height = first.height() * scale;
+
+        out.write_all(b"GIF89a")?;
+        out.write_all(&le16(width))?;
+        out.write_all(&le16(height))?;
+        // A global color table of two entries: off, then on
+        out.write_all(&[0x80, 0, 0])?;
+        for color in &[options.off, options.on] {
+            out.write_all(&[color.r, color.g, color.b])?;
+        }
+        // Loop forever
+        out.write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+
+        for frame in &self.frames {
+            out.write_all(&[0x21, 0xfe])?;
+            write_gif_blocks(&mut out, frame.caption.as_bytes())?;
+
+            out.write_all(&[0x21, 0xf9, 4, 0])?;
+            out.write_all(&le16(options.frame_delay as usize))?;
+            out.write_all(&[0, 0])?;
+
+            out.write_all(&[0x2c, 0, 0, 0, 0])?;
+            out.write_all(&le16(width))?;
+            out.write_all(&le16(height))?;
+            out.write_all(&[0])?;
+
+            let mut pixels = Vec::with_capacity(width * height);
+            for line in &frame.display.leds {
+                let row: Vec<_> = line.iter()
+                    .flat_map(|&on| std::iter::repeat(on as u8).take(scale))
+                    .collect();
+                for _ in 0..scale {
+                    pixels.extend_from_slice(&row);
+                }
+            }
+
+            out.write_all(&[GIF_MIN_CODE_SIZE])?;
+            write_gif_blocks(&mut out, &lzw_encode(&pixels))?;
+        }
+
+        out.write_all(&[0x3b])
+    }
+
+    // Redraws each frame in place with its caption above it
+    fn replay<W: Write>(&self, mut out: W, frame_delay: Duration) -> io::Result<()> {
+        for (i, frame) in self.frames.iter().enumerate() {
+            write!(out, "\x1b[2J\x1b[H")?;
+            writeln!(out, "[{}/{}] {}", i + 1, self.frames.len(), frame.caption)?;
+            write!(out, "{:?}", frame.display)?;
+            out.flush()?;
+            thread::sleep(frame_delay);
+        }
+        Ok(())
+    }
+}
+
+// Draws a single column in column 0, which must start out empty.
+// Runs are placed bottom-most first and pushed down together.
+fn draw_first_column(column: &[bool]) -> Vec<Instruction> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (i, &on) in column.iter().enumerate() {
+        if !on {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(&mut (start, ref mut len)) if start + *len == i => *len += 1,
+            _ => runs.push((i, 1)),
+        }
+    }
+
+    let mut program = Vec::new();
+    for (i, &(start, len)) in runs.iter().enumerate().rev() {
+        program.push(Instruction::Rectangle { x: 1, y: len });
+        let previous_start = if i == 0 { 0 } else { runs[i - 1].0 };
+        if start > previous_start {
+            program.push(Instruction::RotateColumn { idx: 0, amount: (start - previous_start) as isize });
+        }
+    }
+    program
+}
+
+// Builds the target from its rightmost column to its leftmost, each one
+// drawn in column 0. Rows are only rotated when column 0 needs to be
+// cleared or extended, so each row remembers how far its contents still
+// have to move to the right.
+fn synthesize_by_columns(target: &[Vec<bool>]) -> Option<Vec<Instruction>> {
+    let height = target.len();
+    let width = target.first().map_or(0, |line| line.len());
+
+    let mut program = Vec::new();
+    let mut offsets: Vec<Option<usize>> = vec![None; height];
+    let mut in_first_column = vec![false; height];
+
+    for c in (0..width).rev() {
+        let column: Vec<_> = target.iter().map(|line| line[c]).collect();
+        if !column.iter().any(|&on| on) {
+            continue;
+        }
+
+        for (idx, offset) in offsets.iter_mut().enumerate() {
+            if let Some(o) = *offset {
+                if o != c && (in_first_column[idx] || column[idx]) {
+                    program.push(Instruction::RotateRow { idx, amount: (o - c) as isize });
+                    *offset = Some(c);
+                }
+            }
+        }
+
+        program.extend(draw_first_column(&column));
+
+        for (idx, &on) in column.iter().enumerate() {
+            in_first_column[idx] = on;
+            if on {
+                offsets[idx] = Some(c);
+            }
+        }
+    }
+
+    for (idx, offset) in offsets.into_iter().enumerate() {
+        match offset {
+            Some(amount) if amount > 0 => {
+                program.push(Instruction::RotateRow { idx, amount: amount as isize })
+            }
+            _ => {}
+        }
+    }
+
+    Some(program)
+}
+
+// Never turns on an LED that is off in the target, so rectangles may
+// only land on runs of lit LEDs. Each row tracks which target column is
+// currently at its left edge and is only rotated when a rectangle needs a
+// different window of it. Rows below an unlit row can not be reached this
+// way, so this gives up and returns `None`.
+fn synthesize_by_rectangles(target: &[Vec<bool>]) -> Option<Vec<Instruction>> {
+    let height = target.len();
+    let width = target.first().map_or(0, |line| line.len());
+
+    // How many lit LEDs follow each column, wrapping around
+    let runs: Vec<Vec<usize>> = target.iter()
+        .map(|line| {
+            (0..width)
+                .map(|start| (0..width).take_while(|&i| line[(start + i) % width]).count())
+                .collect()
+        })
+        .collect();
+
+    let mut program = Vec::new();
+    let mut placed = vec![vec![false; width]; height];
+    let mut offsets = vec![0; height];
+
+    loop {
+        let lowest = (0..height).rev().find(|&r| (0..width).any(|t| target[r][t] && !placed[r][t]));
+        let lowest = match lowest {
+            Some(r) => r,
+            None => break,
+        };
+
+        // (new LEDs, rotations, width, height, chosen offsets)
+        let mut best: Option<(usize, usize, usize, usize, Vec<usize>)> = None;
+
+        for rect_height in lowest + 1..height + 1 {
+            let widest = (0..rect_height).map(|r| runs[r].iter().cloned().max().unwrap_or(0)).min().unwrap_or(0);
+
+            // Rotating a row for a single new LED may or may not pay off,
+            // so try both ways
+            for (rect_width, patience) in (1..widest + 1).flat_map(|w| vec![(w, 0), (w, 1)]) {
+                let mut gained = 0;
+                let mut rotations = 0;
+                let mut chosen = Vec::with_capacity(rect_height);
+
+                for r in 0..rect_height {
+                    let gain = |o: usize| (o..o + rect_width).filter(|&t| !placed[r][t % width]).count();
+                    let stay = offsets[r];
+                    let mut choice = if runs[r][stay] >= rect_width { Some((gain(stay), 0, stay)) } else { None };
+
+                    for o in (0..width).filter(|&o| runs[r][o] >= rect_width) {
+                        let g = gain(o);
+                        let better = match choice {
+                            None => true,
+                            Some((best_gain, cost, _)) => g > best_gain + patience * (1 - cost) && o != stay,
+                        };
+                        if better {
+                            choice = Some((g, 1, o));
+                        }
+                    }
+
+                    match choice {
+                        Some((g, cost, o)) => {
+                            gained += g;
+                            rotations += cost;
+                            chosen.push(o);
+                        }
+                        None => break,
+                    }
+                }
+
+                if chosen.len() < rect_height || gained == 0 {
+                    continue;
+                }
+
+                // Most new LEDs per instruction, then the most new LEDs
+                let better = match best {
+                    None => true,
+                    Some((g, c, ..)) => gained * (c + 1) > g * (rotations + 1) ||
+                        (gained * (c + 1) == g * (rotations + 1) && gained > g),
+                };
+                if better {
+                    best = Some((gained, rotations, rect_width, rect_height, chosen));
+                }
+            }
+        }
+
+        let (_, _, rect_width, rect_height, chosen) = match best {
+            Some(best) => best,
+            None => return None,
+        };
+
+        for (r, o) in chosen.into_iter().enumerate() {
+            if o != offsets[r] {
+                let amount = (offsets[r] + width - o) % width;
+                program.push(Instruction::RotateRow { idx: r, amount: amount as isize });
+                offsets[r] = o;
+            }
+            for t in o..o + rect_width {
+                placed[r][t % width] = true;
+            }
+        }
+        program.push(Instruction::Rectangle { x: rect_width, y: rect_height });
+    }
+
+    for (r, &offset) in offsets.iter().enumerate() {
+        if offset != 0 && placed[r].iter().any(|&on| on) {
+            program.push(Instruction::RotateRow { idx: r, amount: offset as isize });
+        }
+    }
+
+    Some(program)
+}
+
+fn transpose(leds: &[Vec<bool>]) -> Vec<Vec<bool>> {
+    let width = leds.first().map_or(0, |line| line.len());
+    (0..width).map(|x| leds.iter().map(|line| line[x]).collect()).collect()
+}
+
+fn transpose_instruction(instruction: Instruction) -> Instruction {
+    use Instruction::*;
+
+    match instruction {
+        Rectangle { x, y } => Rectangle { x: y, y: x },
+        RotateColumn { idx, amount } => RotateRow { idx, amount },
+        RotateRow { idx, amount } => RotateColumn { idx, amount },
+        other => other,
+    }
+}
+
+// A program of `rect` and `rotate` instructions that draws `target` on a
+// blank display of the same size. Every strategy is tried in both
+// orientations and the shortest program wins.
+fn synthesize(target: &Display) -> Vec<Instruction> {
+    let strategies: &[fn(&[Vec<bool>]) -> Option<Vec<Instruction>>] = &[
+        synthesize_by_rectangles,
+        synthesize_by_columns,
+    ];
+    let transposed = transpose(&target.leds);
+
+    let mut programs = Vec::new();
+    for strategy in strategies {
+        programs.extend(strategy(&target.leds));
+        programs.extend(strategy(&transposed).map(|program| {
+            program.into_iter().map(transpose_instruction).collect()
+        }));
+    }
+
+    programs.into_iter().min_by_key(Vec::len).expect("No strategy produced a program")
+}
+
+// Runs a program on a screen of the given size, e.g. `5000x1000`
+fn run_packed(size: &str, program: &str, bounds: Bounds) -> Result<usize, Box<Error>> {
+    let mut size = size.split('x');
+    let width = size.next().ok_or("Missing width")?.parse()?;
+    let height = size.next().ok_or("Missing height")?.parse()?;
+    if width == 0 || height == 0 {
+        return Err("The screen must be at least 1x1".into());
+    }
+
+    let mut display = PackedDisplay::new(width, height);
+    for line in BufReader::new(File::open(program)?).lines() {
+        display.process(line?.parse()?, bounds)?;
+    }
+    Ok(display.count())
+}
+
+const REPLAY_DELAY_MS: u64 = 100;
+
+// The delay between frames at a speed such as `2` for twice as fast
+fn replay_delay(speed: &str) -> Result<Duration, Box<Error>> {
+    let speed: f64 = speed.parse()?;
+    if !(speed.is_finite() && speed > 0.0) {
+        return Err(format!("Speed must be a positive number, not {}", speed).into());
+    }
+
+    let ms = REPLAY_DELAY_MS as f64 / speed;
+    if ms >= std::u64::MAX as f64 {
+        return Err(format!("Speed {} is too slow", speed).into());
+    }
+    Ok(Duration::from_millis(ms as u64))
+}
+
+fn main() {
+    let mut args: Vec<_> = env::args().skip(1).collect();
+
+    // `--clip` applies as much of each instruction as fits on the screen
+    let bounds = if args.first().map(String::as_str) == Some("--clip") {
+        args.remove(0);
+        Bounds::Clip
+    } else {
+        Bounds::Strict
+    };
+
+    if args.first().map(String::as_str) == Some("--packed") {
+        let size = args.get(1).expect("A screen size is required");
+        let program = args.get(2).expect("A program file is required");
+        let count = run_packed(size, program, bounds).expect("Unable to run program");
+        println!("There are {} lights", count);
+        return;
+    }
+
+    if args.first().map(String::as_str) == Some("--synthesize") {
+        let path = args.get(1).expect("A target display is required");
+        let mut target = String::new();
+        File::open(path)
+            .and_then(|mut f| io::Read::read_to_string(&mut f, &mut target))
+            .expect("Unable to read target display");
+        let target: Display = target.parse().expect("Invalid target display");
+
+        let program = synthesize(&target);
+
+        let mut check = Display::new(target.width(), target.height());
+        for &instruction in &program {
+            check.process(instruction).expect("Synthesized instruction is out of bounds");
+        }
+        assert_eq!(check, target, "Synthesized program does not draw the target");
+
+        for instruction in program {
+            println!("{}", instruction);
+        }
+        return;
+    }
+
+    let input = include_str!("input.txt");
+    let mut display = Display::new(50, 6);
+    let mut recording = Recording::new(&display);
+    for line in input.lines() {
+        display.process_recorded(line.parse().expect("Coldn't parse instruction"), bounds, &mut recording)
+            .expect("Instruction does not fit on the display");
+    }
+
+    // `--replay` plays back at the given speed, where 2 is twice as fast
+    if args.first().map(String::as_str) == Some("--replay") {
+        let delay = args.get(1).map_or(Ok(Duration::from_millis(REPLAY_DELAY_MS)), |s| replay_delay(s))
+            .expect("Invalid speed");
+        recording.replay(io::stdout(), delay).expect("Unable to replay");
+        return;
+    }
+
+    println!("{:?}", display);
+    println!("There are {} lights", display.count());
+    match display.read_text() {
+        Ok(text) => println!("The code is {}", text),
+        Err(e) => println!("Unable to read the code: {}", e),
+    }
+
+    for path in args {
+        if path.ends_with(".gif") {
+            let out = BufWriter::new(File::create(&path).expect("Unable to create animation"));
+            recording.write_gif(out, &GifOptions::default()).expect("Unable to export animation");
+        } else {
+            display.export(&path).expect("Unable to export display");
+        }
+        println!("Wrote {}", path);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{crc32, Display, Instruction, PngOptions, SvgOptions, Color};
+    use super::{OcrError, UnknownGlyph, PackedDisplay};
+    use super::{lzw_encode, GifOptions, Recording};
+    use super::{run_packed, synthesize, rotated, shifted, replay_delay};
+    use super::{Bounds, OutOfBounds, DisplayParseError};
+    use std::time::Duration;
+
+    fn quick_display(s: &str) -> Display {
+        s.parse().expect("Unable to parse test display")
+    }
+
+    #[test]
+    fn example_1() {
+        let mut display = Display::new(7, 3);
+
+        display.process(Instruction::Rectangle { x: 3, y: 2 }).expect("Instruction is out of bounds");
+
+        let expected = quick_display("###....\n\
+                                      ###....\n\
+                                      .......");
+
+        assert_eq!(display, expected);
+    }
+
+    #[test]
+    fn example_2() {
+        let mut display = quick_display("###....\n\
+                                         ###....\n\
+                                         .......");
+
+        display.process(Instruction::RotateColumn { idx: 1, amount: 1 }).expect("Instruction is out of bounds");
+
+        let expected = quick_display("#.#....\n\
+                                      ###....\n\
+                                      .#.....");
+
+        assert_eq!(display, expected);
+    }
+
+    #[test]
+    fn example_3() {
+        let mut display = quick_display("#.#....\n\
+                                         ###....\n\
+                                         .#.....");
+
+        display.process(Instruction::RotateRow { idx: 0, amount: 4 }).expect("Instruction is out of bounds");
+
+        let expected = quick_display("....#.#\n\
+                                      ###....\n\
+                                      .#.....");
+
+        assert_eq!(display, expected);
+    }
+
+    #[test]
+    fn example_4() {
+        let mut display = quick_display("....#.#\n\
+                                         ###....\n\
+                                         .#.....");
+
+        display.process(Instruction::RotateColumn { idx: 1, amount: 1 }).expect("Instruction is out of bounds");
+
+        let expected = quick_display(".#..#.#\n\
+                                      #.#....\n\
+                                      .#.....\n");
+
+        assert_eq!(display, expected);
+    }
+
+    #[test]
+    fn instruction_1() {
+        let actual: Instruction = "rect 100x50".parse().expect("Could not parse instruction");
+        assert_eq!(actual, Instruction::Rectangle { x: 100, y: 50 });
+    }
+
+    #[test]
+    fn instruction_2() {
+        let actual: Instruction = "rotate row y=99 by 42".parse().expect("Could not parse instruction");
+        assert_eq!(actual, Instruction::RotateRow { idx: 99, amount: 42 });
+    }
+
+    #[test]
+    fn instruction_3() {
+        let actual: Instruction = "rotate column x=42 by 99".parse().expect("Could not parse instruction");
+        assert_eq!(actual, Instruction::RotateColumn { idx: 42, amount: 99 });
+    }
+
+    #[test]
+    fn export_pbm() {
+        let display = quick_display("#.#\n\
+                                     .#.");
+        let mut out = Vec::new();
+        display.write_pbm(&mut out).expect("Unable to write PBM");
+
+        assert_eq!(String::from_utf8(out).expect("Invalid UTF-8"), "P1\n3 2\n1 0 1\n0 1 0\n");
+    }
+
+    fn be32(bytes: &[u8]) -> usize {
+        bytes[..4].iter().fold(0, |acc, &b| acc << 8 | b as usize)
+    }
+
+    // Returns the IHDR data and the inflated image data
+    fn read_png(mut png: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        png = &png[8..];
+
+        let mut header = Vec::new();
+        let mut zlib = Vec::new();
+        while !png.is_empty() {
+            let len = be32(png);
+            let (kind_and_data, rest) = png[4..].split_at(4 + len);
+            assert_eq!(be32(rest), crc32(kind_and_data) as usize);
+            match &kind_and_data[..4] {
+                b"IHDR" => header.extend_from_slice(&kind_and_data[4..]),
+                b"IDAT" => zlib.extend_from_slice(&kind_and_data[4..]),
+                _ => {}
+            }
+            png = &rest[4..];
+        }
+
+        let mut stored = &zlib[2..];
+        let mut data = Vec::new();
+        loop {
+            let last = stored[0] & 1 == 1;
+            let len = stored[1] as usize | (stored[2] as usize) << 8;
+            data.extend_from_slice(&stored[5..][..len]);
+            stored = &stored[5 + len..];
+            if last {
+                break;
+            }
+        }
+
+        (header, data)
+    }
+
+    #[test]
+    fn export_png() {
+        let display = quick_display("#.\n\
+                                     ..");
+        let on = Color { r: 1, g: 2, b: 3 };
+        let off = Color { r: 4, g: 5, b: 6 };
+        let mut out = Vec::new();
+        display.write_png(&mut out, &PngOptions { scale: 2, on, off }).expect("Unable to write PNG");
+
+        let (header, data) = read_png(&out);
+        assert_eq!(be32(&header[0..]), 4);
+        assert_eq!(be32(&header[4..]), 4);
+        assert_eq!(data.len(), 4 * (1 + 4 * 3));
+        assert_eq!(&data[..13], &[0, 1, 2, 3, 1, 2, 3, 4, 5, 6, 4, 5, 6]);
+        assert_eq!(&data[13..26], &data[..13]);
+        assert_eq!(&data[39..], &[0, 4, 5, 6, 4, 5, 6, 4, 5, 6, 4, 5, 6]);
+    }
+
+    #[test]
+    fn export_svg() {
+        let display = quick_display("#.\n\
+                                     .#");
+        let mut out = Vec::new();
+        display.write_svg(&mut out, &SvgOptions::default()).expect("Unable to write SVG");
+        let svg = String::from_utf8(out).expect("Invalid UTF-8");
+
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches(r#"rx="5""#).count(), 4);
+        assert_eq!(svg.matches("#5fff5f").count(), 2);
+    }
+
+    #[test]
+    fn read_text() {
+        let display = quick_display("#..#..###.#...#\n\
+                                     #..#...#..#...#\n\
+                                     ####...#...#.#.\n\
+                                     #..#...#....#..\n\
+                                     #..#...#....#..\n\
+                                     #..#..###...#..");
+
+        assert_eq!(display.read_text(), Ok("HIY".to_owned()));
+    }
+
+    #[test]
+    fn read_text_of_input() {
+        let mut display = Display::new(50, 6);
+        for line in include_str!("input.txt").lines() {
+            display.process(line.parse().expect("Unable to parse instruction")).expect("Instruction is out of bounds");
+        }
+
+        assert_eq!(display.read_text(), Ok("RURUCEOEIL".to_owned()));
+    }
+
+    #[test]
+    fn read_text_reports_unknown_glyphs() {
+        let display = quick_display("#..#.#####\n\
+                                     #..#.#####\n\
+                                     ####.#####\n\
+                                     #..#.#####\n\
+                                     #..#.#####\n\
+                                     #..#.#####");
+
+        let expected = OcrError::UnknownGlyphs {
+            text: "H?".to_owned(),
+            glyphs: vec![UnknownGlyph { index: 1, column: 5, rows: vec!["#####".to_owned(); 6] }],
+        };
+        assert_eq!(display.read_text(), Err(expected));
+    }
+
+    #[test]
+    fn read_text_requires_glyph_height() {
+        let display = Display::new(5, 5);
+        assert_eq!(display.read_text(), Err(OcrError::WrongHeight { height: 5 }));
+    }
+
+    #[test]
+    fn rectangle_off() {
+        let mut display = quick_display("#######\n\
+                                         #######\n\
+                                         #######");
+
+        display.process(Instruction::RectangleOff { x: 3, y: 2 }).expect("Instruction is out of bounds");
+
+        let expected = quick_display("...####\n\
+                                      ...####\n\
+                                      #######");
+
+        assert_eq!(display, expected);
+    }
+
+    #[test]
+    fn toggle() {
+        let mut display = quick_display("#.#....\n\
+                                         ###....\n\
+                                         .#.....");
+
+        display.process(Instruction::Toggle { x: 3, y: 2 }).expect("Instruction is out of bounds");
+
+        let expected = quick_display(".#.....\n\
+                                      .......\n\
+                                      .#.....");
+
+        assert_eq!(display, expected);
+    }
+
+    #[test]
+    fn invert() {
+        let mut display = quick_display("#.#....\n\
+                                         ###....\n\
+                                         .#.....");
+
+        display.process(Instruction::Invert).expect("Instruction is out of bounds");
+
+        let expected = quick_display(".#.####\n\
+                                      ...####\n\
+                                      #.#####");
+
+        assert_eq!(display, expected);
+    }
+
+    #[test]
+    fn shift_row() {
+        let mut display = quick_display("#.#...#\n\
+                                         ###....\n\
+                                         .#.....");
+
+        display.process(Instruction::ShiftRow { idx: 0, amount: 2 }).expect("Instruction is out of bounds");
+        display.process(Instruction::ShiftRow { idx: 1, amount: -1 }).expect("Instruction is out of bounds");
+
+        let expected = quick_display("..#.#..\n\
+                                      ##.....\n\
+                                      .#.....");
+
+        assert_eq!(display, expected);
+    }
+
+    #[test]
+    fn shift_column() {
+        let mut display = quick_display("#.#....\n\
+                                         ###....\n\
+                                         .#.....");
+
+        display.process(Instruction::ShiftColumn { idx: 0, amount: 1 }).expect("Instruction is out of bounds");
+        display.process(Instruction::ShiftColumn { idx: 1, amount: -1 }).expect("Instruction is out of bounds");
+
+        let expected = quick_display(".##....\n\
+                                      ###....\n\
+                                      #......");
+
+        assert_eq!(display, expected);
+    }
+
+    #[test]
+    fn negative_rotations() {
+        let mut display = quick_display("....#.#\n\
+                                         ###....\n\
+                                         .#.....");
+
+        display.process(Instruction::RotateRow { idx: 0, amount: -4 }).expect("Instruction is out of bounds");
+        display.process(Instruction::RotateColumn { idx: 1, amount: -1 }).expect("Instruction is out of bounds");
+
+        let expected = quick_display("###....\n\
+                                      ###....\n\
+                                      .......");
+
+        assert_eq!(display, expected);
+    }
+
+    #[test]
+    fn instruction_4() {
+        let actual: Instruction = "rect off 3x2".parse().expect("Could not parse instruction");
+        assert_eq!(actual, Instruction::RectangleOff { x: 3, y: 2 });
+    }
+
+    #[test]
+    fn instruction_5() {
+        let actual: Instruction = "toggle 4x1".parse().expect("Could not parse instruction");
+        assert_eq!(actual, Instruction::Toggle { x: 4, y: 1 });
+    }
+
+    #[test]
+    fn instruction_6() {
+        let actual: Instruction = "invert".parse().expect("Could not parse instruction");
+        assert_eq!(actual, Instruction::Invert);
+    }
+
+    #[test]
+    fn instruction_7() {
+        let actual: Instruction = "shift row y=2 by -3".parse().expect("Could not parse instruction");
+        assert_eq!(actual, Instruction::ShiftRow { idx: 2, amount: -3 });
+    }
+
+    #[test]
+    fn instruction_8() {
+        let actual: Instruction = "shift column x=5 by 1".parse().expect("Could not parse instruction");
+        assert_eq!(actual, Instruction::ShiftColumn { idx: 5, amount: 1 });
+    }
+
+    #[test]
+    fn moves_by_extreme_amounts() {
+        use std::isize;
+
+        // 2^63 is one more than a multiple of 7
+        assert_eq!(rotated(2, isize::MAX, 7), 2);
+        assert_eq!(rotated(2, isize::MIN, 7), 1);
+        assert_eq!(shifted(2, isize::MAX, 7), None);
+        assert_eq!(shifted(2, isize::MIN, 7), None);
+
+        let instruction: Instruction = "rotate row y=0 by 9223372036854775807".parse()
+            .expect("Could not parse instruction");
+        assert_eq!(instruction, Instruction::RotateRow { idx: 0, amount: isize::MAX });
+    }
+
+    #[test]
+    fn instruction_9() {
+        let actual: Instruction = "rotate column x=1 by -7".parse().expect("Could not parse instruction");
+        assert_eq!(actual, Instruction::RotateColumn { idx: 1, amount: -7 });
+    }
+
+    fn random_instructions(width: usize, height: usize, count: usize, mut seed: u32) -> Vec<Instruction> {
+        let mut next = move |limit: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as usize % limit
+        };
+
+        (0..count).map(|_| {
+            let amount = next(3 * width) as isize - width as isize;
+            match next(8) {
+                0 => Instruction::Rectangle { x: next(width + 1), y: next(height + 1) },
+                1 => Instruction::RectangleOff { x: next(width + 1), y: next(height + 1) },
+                2 => Instruction::Toggle { x: next(width + 1), y: next(height + 1) },
+                3 => Instruction::Invert,
+                4 => Instruction::RotateColumn { idx: next(width), amount },
+                5 => Instruction::RotateRow { idx: next(height), amount },
+                6 => Instruction::ShiftColumn { idx: next(width), amount },
+                _ => Instruction::ShiftRow { idx: next(height), amount },
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn packed_matches_display() {
+        for &bounds in &[Bounds::Strict, Bounds::Clip] {
+            for &(width, height) in &[(7, 3), (50, 6), (64, 4), (130, 9)] {
+                let mut display = Display::new(width, height);
+                let mut packed = PackedDisplay::new(width, height);
+
+                // Some of the instructions are too large for the display
+                let seed = width as u32 * 31 + 7;
+                for instruction in random_instructions(width + 3, height + 3, 2000, seed) {
+                    assert_eq!(packed.process(instruction, bounds),
+                               display.process_with(instruction, bounds),
+                               "{:?}", instruction);
+                    assert_eq!(Display::from(&packed), display, "{:?}", instruction);
+                }
+                assert_eq!(packed.count(), display.count());
+            }
+        }
+    }
+
+    #[test]
+    fn packed_rejects_what_does_not_fit() {
+        let mut packed = PackedDisplay::new(7, 3);
+        let instruction = Instruction::RotateRow { idx: 3, amount: 1 };
+        assert_eq!(packed.process(instruction, Bounds::Strict),
+                   Err(OutOfBounds { instruction, width: 7, height: 3 }));
+        assert!(packed.process(Instruction::Rectangle { x: 8, y: 1 }, Bounds::Strict).is_err());
+        assert_eq!(packed.process(Instruction::Rectangle { x: 8, y: 1 }, Bounds::Clip), Ok(()));
+        assert_eq!(packed.count(), 7);
+    }
+
+    #[test]
+    fn packed_matches_display_for_extreme_amounts() {
+        use std::isize;
+
+        for &amount in &[isize::MAX, isize::MIN, isize::MIN + 1] {
+            let instructions = [
+                Instruction::RotateColumn { idx: 1, amount },
+                Instruction::RotateRow { idx: 0, amount },
+                Instruction::ShiftColumn { idx: 1, amount },
+                Instruction::ShiftRow { idx: 0, amount },
+            ];
+
+            for &instruction in &instructions {
+                let mut display = Display::new(70, 3);
+                let mut packed = PackedDisplay::new(70, 3);
+                for &setup in &[Instruction::Rectangle { x: 5, y: 2 }, Instruction::Toggle { x: 66, y: 1 }] {
+                    display.process(setup).expect("Instruction is out of bounds");
+                    packed.process(setup, Bounds::Strict).expect("Instruction is out of bounds");
+                }
+
+                display.process(instruction).expect("Instruction is out of bounds");
+                packed.process(instruction, Bounds::Strict).expect("Instruction is out of bounds");
+                assert_eq!(Display::from(&packed), display, "{:?}", instruction);
+            }
+        }
+    }
+
+    #[test]
+    fn replay_speeds() {
+        assert_eq!(replay_delay("1").ok(), Some(Duration::from_millis(100)));
+        assert_eq!(replay_delay("2").ok(), Some(Duration::from_millis(50)));
+        assert_eq!(replay_delay("0.5").ok(), Some(Duration::from_millis(200)));
+
+        for speed in &["0", "-1", "-0", "inf", "NaN", "1e-300", "fast"] {
+            assert!(replay_delay(speed).is_err(), "{}", speed);
+        }
+    }
+
+    #[test]
+    fn packed_handles_empty_displays() {
+        for &(width, height) in &[(0, 3), (4, 0), (0, 0)] {
+            let mut packed = PackedDisplay::new(width, height);
+            for instruction in random_instructions(5, 5, 100, 17) {
+                let _ = packed.process(instruction, Bounds::Clip);
+            }
+            assert_eq!(packed.count(), 0);
+        }
+
+        let mut packed = PackedDisplay::new(0, 3);
+        assert_eq!(packed.process(Instruction::RotateRow { idx: 0, amount: 1 }, Bounds::Strict), Ok(()));
+
+        let error = run_packed("0x6", "missing-program.txt", Bounds::Strict).unwrap_err();
+        assert_eq!(error.to_string(), "The screen must be at least 1x1");
+    }
+
+    #[test]
+    fn packed_runs_input() {
+        let mut packed = PackedDisplay::new(50, 6);
+        for line in include_str!("input.txt").lines() {
+            packed.process(line.parse().expect("Unable to parse instruction"), Bounds::Strict)
+                .expect("Instruction is out of bounds");
+        }
+
+        assert_eq!(packed.count(), 121);
+    }
+
+    #[test]
+    fn instructions_round_trip() {
+        let program = "rect 3x2\n\
+                       rect off 1x1\n\
+                       toggle 4x1\n\
+                       invert\n\
+                       rotate column x=1 by -1\n\
+                       rotate row y=0 by 4\n\
+                       shift column x=2 by 1\n\
+                       shift row y=1 by -2";
+
+        for line in program.lines() {
+            let instruction: Instruction = line.parse().expect("Could not parse instruction");
+            assert_eq!(instruction.to_string(), line);
+        }
+    }
+
+    fn recorded_examples() -> Recording {
+        let mut display = Display::new(7, 3);
+        let mut recording = Recording::new(&display);
+        for &instruction in &[
+            Instruction::Rectangle { x: 3, y: 2 },
+            Instruction::RotateColumn { idx: 1, amount: 1 },
+            Instruction::RotateRow { idx: 0, amount: 4 },
+            Instruction::RotateColumn { idx: 1, amount: 1 },
+        ] {
+            display.process_recorded(instruction, Bounds::Strict, &mut recording)
+                .expect("Instruction is out of bounds");
+        }
+        recording
+    }
+
+    #[test]
+    fn record_frames() {
+        let recording = recorded_examples();
+
+        assert_eq!(recording.frames.len(), 5);
+        assert_eq!(recording.frames[0].display, Display::new(7, 3));
+        assert_eq!(recording.frames[3].caption, "rotate row y=0 by 4");
+        assert_eq!(recording.frames[3].display, quick_display("....#.#\n\
+                                                               ###....\n\
+                                                               .#....."));
+    }
+
+    // A straightforward GIF LZW decoder to check the encoder against
+    fn lzw_decode(data: &[u8]) -> Vec<u8> {
+        let (clear, end) = (4, 5);
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut bits = 3;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut pixels = Vec::new();
+
+        let (mut acc, mut acc_bits, mut bytes) = (0u32, 0, data.iter());
+        loop {
+            while acc_bits < bits {
+                acc |= (*bytes.next().expect("Ran out of data") as u32) << acc_bits;
+                acc_bits += 8;
+            }
+            let code = (acc & ((1 << bits) - 1)) as usize;
+            acc >>= bits;
+            acc_bits -= bits;
+
+            if code == clear {
+                table = (0..4).map(|c| vec![c as u8]).collect();
+                table.push(vec![]);
+                table.push(vec![]);
+                bits = 3;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return pixels;
+            }
+
+            let entry = match previous {
+                None => table[code].clone(),
+                Some(ref previous) => {
+                    let entry = if code < table.len() {
+                        table[code].clone()
+                    } else {
+                        let mut entry = previous.clone();
+                        entry.push(previous[0]);
+                        entry
+                    };
+                    let mut added = previous.clone();
+                    added.push(entry[0]);
+                    table.push(added);
+                    entry
+                }
+            };
+            if table.len() == 1 << bits && bits < 12 {
+                bits += 1;
+            }
+
+            pixels.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        let mut seed = 12345u32;
+        let random: Vec<u8> = (0..20_000).map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) as u8 & 1
+        }).collect();
+
+        for pixels in &[vec![], vec![1], vec![0, 0, 0, 1, 1, 1, 0, 1], vec![1; 50_000], random] {
+            assert_eq!(&lzw_decode(&lzw_encode(pixels)), pixels);
+        }
+    }
+
+    #[test]
+    fn export_gif() {
+        let mut out = Vec::new();
+        recorded_examples().write_gif(&mut out, &GifOptions::default()).expect("Unable to write GIF");
+
+        assert_eq!(&out[..6], b"GIF89a");
+        assert_eq!(&out[6..10], &[56, 0, 24, 0]);
+        assert_eq!(out.last(), Some(&0x3b));
+        assert_eq!(out.windows(2).filter(|w| w == &[0x21, 0xf9]).count(), 5);
+        assert!(out.windows(8).any(|w| w == b"rect 3x2"));
+    }
+
+    #[test]
+    fn replay_shows_captions() {
+        let mut out = Vec::new();
+        recorded_examples().replay(&mut out, Duration::from_millis(0)).expect("Unable to replay");
+        let replay = String::from_utf8(out).expect("Invalid UTF-8");
+
+        assert!(replay.contains("[1/5] start"));
+        assert!(replay.contains("[5/5] rotate column x=1 by 1\n.#..#.#\n"));
+    }
+
+    fn run(width: usize, height: usize, program: &[Instruction]) -> Display {
+        let mut display = Display::new(width, height);
+        for &instruction in program {
+            display.process(instruction).expect("Instruction is out of bounds");
+        }
+        display
+    }
+
+    #[test]
+    fn synthesize_example() {
+        let target = quick_display(".#..#.#\n\
+                                    #.#....\n\
+                                    .#.....");
+
+        let program = synthesize(&target);
+        assert_eq!(run(7, 3, &program), target);
+        assert!(program.len() < 2 * target.count(), "{:?}", program);
+    }
+
+    #[test]
+    fn synthesize_input() {
+        let input = include_str!("input.txt");
+        let target = run(50, 6, &input.lines()
+            .map(|l| l.parse().expect("Unable to parse instruction"))
+            .collect::<Vec<_>>());
+
+        let program = synthesize(&target);
+        assert_eq!(run(50, 6, &program), target);
+        assert!(program.len() < input.lines().count());
+    }
+
+    #[test]
+    fn synthesize_random_targets() {
+        let mut seed = 0x9e37_79b9u32;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed
+        };
+
+        for _ in 0..200 {
+            let width = 1 + next() as usize % 12;
+            let height = 1 + next() as usize % 8;
+            let density = next() % 4;
+            let mut target = Display::new(width, height);
+            for line in &mut target.leds {
+                for led in line {
+                    *led = next() % 4 < density;
+                }
+            }
+
+            let program = synthesize(&target);
+            assert_eq!(run(width, height, &program), target, "{:?}", program);
+            assert!(program.iter().all(|i| i.to_string().parse::<Instruction>().ok() == Some(*i)));
+        }
+    }
+
+    #[test]
+    fn out_of_bounds_is_an_error() {
+        let mut display = Display::new(50, 6);
+
+        for &instruction in &[
+            Instruction::Rectangle { x: 100, y: 50 },
+            Instruction::Toggle { x: 3, y: 7 },
+            Instruction::RotateRow { idx: 99, amount: 1 },
+            Instruction::ShiftColumn { idx: 50, amount: 1 },
+        ] {
+            let error = OutOfBounds { instruction, width: 50, height: 6 };
+            assert_eq!(display.process(instruction), Err(error));
+        }
+        assert_eq!(display, Display::new(50, 6));
+
+        let error = display.process(Instruction::RotateRow { idx: 99, amount: 1 }).unwrap_err();
+        assert_eq!(error.to_string(), "`rotate row y=99 by 1` does not fit on a 50x6 display");
+    }
+
+    #[test]
+    fn clipping_applies_what_fits() {
+        let mut display = Display::new(7, 3);
+
+        display.process_with(Instruction::Rectangle { x: 2, y: 10 }, Bounds::Clip).expect("Clipping failed");
+        display.process_with(Instruction::RotateRow { idx: 99, amount: 1 }, Bounds::Clip).expect("Clipping failed");
+        display.process_with(Instruction::Toggle { x: 10, y: 1 }, Bounds::Clip).expect("Clipping failed");
+
+        let expected = quick_display("..#####\n\
+                                      ##.....\n\
+                                      ##.....");
+
+        assert_eq!(display, expected);
+    }
+
+    #[test]
+    fn extreme_amounts_do_not_panic() {
+        use std::isize;
+
+        for &amount in &[isize::MAX, isize::MIN] {
+            let mut display = quick_display("#......\n\
+                                             .......\n\
+                                             .......");
+            for &bounds in &[Bounds::Strict, Bounds::Clip] {
+                for &instruction in &[Instruction::RotateRow { idx: 0, amount },
+                                      Instruction::RotateColumn { idx: 0, amount },
+                                      Instruction::ShiftRow { idx: 1, amount },
+                                      Instruction::ShiftColumn { idx: 1, amount }] {
+                    assert_eq!(display.process_with(instruction, bounds), Ok(()));
+                }
+            }
+        }
+
+        let mut display = Display::new(7, 3);
+        let program = "rect 1x1\n\
+                       rotate row y=0 by -9223372036854775808\n\
+                       rotate column x=6 by 9223372036854775807";
+        for line in program.lines() {
+            display.process(line.parse().expect("Could not parse instruction")).expect("Instruction is out of bounds");
+        }
+        // -2^63 is -1 mod 7, and 2^63 - 1 is 1 mod 3
+        assert_eq!(display, quick_display(".......\n\
+                                           ......#\n\
+                                           ......."));
+    }
+
+    #[test]
+    fn parse_rejects_ragged_rows() {
+        let error = "###....\n\
+                     ###...\n\
+                     .......".parse::<Display>();
+
+        assert_eq!(error, Err(DisplayParseError::RaggedRow { line: 2, column: 7, expected: 7, actual: 6 }));
+    }
+
+    #[test]
+    fn parse_rejects_unknown_characters() {
+        let error = "###....\n\
+                     ##x....".parse::<Display>();
+
+        assert_eq!(error, Err(DisplayParseError::UnknownCharacter { line: 2, column: 3, character: 'x' }));
+        assert_eq!(error.unwrap_err().to_string(), "2:3: unknown LED character 'x'");
+    }
+
+    #[test]
+    fn parse_block_characters() {
+        let display = quick_display("███░░░░\n\
+                                     ░█░░░░░");
+
+        let expected = quick_display("###....\n\
+                                      .#.....");
+
+        assert_eq!(display, expected);
+    }
+
+    #[test]
+    fn parse_header() {
+        let display = quick_display("7x2\n\
+                                     ###....\n\
+                                     .#.....");
+        assert_eq!((display.width(), display.height()), (7, 2));
+
+        assert_eq!(quick_display("50x6"), Display::new(50, 6));
+
+        let error = "4x1\n\
+                     ###....".parse::<Display>();
+        assert_eq!(error, Err(DisplayParseError::RaggedRow { line: 2, column: 5, expected: 4, actual: 7 }));
+
+        let error = "7x3\n\
+                     ###....".parse::<Display>();
+        assert_eq!(error, Err(DisplayParseError::WrongHeight { expected: 3, actual: 1 }));
+
+        assert_eq!("0x6".parse::<Display>(), Err(DisplayParseError::EmptySize { width: 0, height: 6 }));
+        assert_eq!("7x0".parse::<Display>(), Err(DisplayParseError::EmptySize { width: 7, height: 0 }));
     }
 }